@@ -1,7 +1,7 @@
 
 pub mod string {
     use std::ptr;
-    pub fn unsafe_copy(string: &String)-> String {
+    pub fn unsafe_copy(string: &str)-> String {
         unsafe {
             from_buf_raw(string.as_ptr(), string.len())
         }
@@ -9,11 +9,7 @@ pub mod string {
 
     pub fn unsafe_copy_option_str(string_o: &Option<String>)-> Option<String> {
         unsafe {
-            if let Some (string) = string_o {
-                Some(from_buf_raw(string.as_ptr(), string.len()))
-            } else {
-                None
-            }
+            string_o.as_ref().map(|string| from_buf_raw(string.as_ptr(), string.len()))
         }
     }
     
@@ -29,7 +25,7 @@ pub mod string {
 
 pub mod vector {
     use std::ptr;
-    pub fn unsafe_copy<T>(vec: &Vec<T>)-> Vec<T> {
+    pub fn unsafe_copy<T>(vec: &[T])-> Vec<T> {
         unsafe {
             from_buf_raw(vec.as_ptr(), vec.len())
         }
@@ -55,7 +51,9 @@ mod tests {
 
     #[test]
     fn it_works() {
-        let result = add(2, 2);
-        assert_eq!(result, 4);
+        let result = string::unsafe_copy("admin");
+        assert_eq!(result, "admin");
+        assert_eq!(string::unsafe_copy_option_str(&None), None);
+        assert_eq!(vector::unsafe_copy(&[1u64, 2, 3]), vec![1, 2, 3]);
     }
 }
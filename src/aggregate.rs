@@ -0,0 +1,195 @@
+use std::collections::HashSet;
+use std::ops::ControlFlow;
+use sqlparser::ast::{BinaryOperator, Expr, Function, FunctionArg, FunctionArgExpr, Query, Visit, Visitor};
+use crate::error::DbError;
+use crate::expression::{ColumnLookup, eval_binary_op, eval_expr, eval_predicate};
+use crate::value::Value;
+
+const AGGREGATE_FUNCTIONS: [&str; 7] = ["COUNT", "SUM", "AVG", "MIN", "MAX", "STRING_AGG", "GROUP_CONCAT"];
+
+/// Whether `function` folds the rows of a group into one value, window calls (with OVER) excluded.
+pub fn is_aggregate(function: &Function) -> bool {
+    function.over.is_none() && is_aggregate_name(&function.name.to_string())
+}
+
+/// Whether `name` is the name of an aggregate function.
+pub fn is_aggregate_name(name: &str) -> bool {
+    AGGREGATE_FUNCTIONS.contains(&name.to_uppercase().as_str())
+}
+
+/// Collects the aggregate calls of expressions, leaving out those of nested subqueries.
+struct AggregateCollector {
+    query_depth: usize,
+    found: Vec<Expr>,
+}
+
+impl Visitor for AggregateCollector {
+    type Break = ();
+
+    fn pre_visit_query(&mut self, _query: &Query) -> ControlFlow<Self::Break> {
+        self.query_depth += 1;
+        ControlFlow::Continue(())
+    }
+
+    fn post_visit_query(&mut self, _query: &Query) -> ControlFlow<Self::Break> {
+        self.query_depth -= 1;
+        ControlFlow::Continue(())
+    }
+
+    fn pre_visit_expr(&mut self, expr: &Expr) -> ControlFlow<Self::Break> {
+        if let Expr::Function(function) = expr {
+            if self.query_depth == 0 && is_aggregate(function) && !self.found.contains(expr) {
+                self.found.push(expr.clone());
+            }
+        }
+        ControlFlow::Continue(())
+    }
+}
+
+/// Distinct aggregate calls appearing in `exprs`.
+pub fn collect_aggregates<'a>(exprs: impl IntoIterator<Item = &'a Expr>) -> Vec<Expr> {
+    let mut collector = AggregateCollector { query_depth: 0, found: Vec::new() };
+    for expr in exprs {
+        let _ = expr.visit(&mut collector);
+    }
+    collector.found
+}
+
+enum AggregateState {
+    Count(i64),
+    Sum(Option<Value>),
+    Avg(Option<Value>, i64),
+    Min(Option<Value>),
+    Max(Option<Value>),
+    /// (ORDER BY values, separator, text) of every aggregated value.
+    StringAgg(Vec<(Vec<Value>, String, String)>),
+}
+
+/// Running state of one aggregate call over the rows of a group.
+pub struct Accumulator<'a> {
+    function: &'a Function,
+    distinct_values: Option<HashSet<Value>>,
+    state: AggregateState,
+}
+
+impl<'a> Accumulator<'a> {
+    pub fn new(function: &'a Function) -> Result<Self, DbError> {
+        let function_name = function.name.to_string().to_uppercase();
+        let (state, arg_counts): (AggregateState, &[usize]) = match function_name.as_str() {
+            "COUNT" => (AggregateState::Count(0), &[1]),
+            "SUM" => (AggregateState::Sum(None), &[1]),
+            "AVG" => (AggregateState::Avg(None, 0), &[1]),
+            "MIN" => (AggregateState::Min(None), &[1]),
+            "MAX" => (AggregateState::Max(None), &[1]),
+            "STRING_AGG" => (AggregateState::StringAgg(Vec::new()), &[2]),
+            "GROUP_CONCAT" => (AggregateState::StringAgg(Vec::new()), &[1, 2]),
+            _ => { return Err(DbError::Unsupported(format!("aggregate function {}", function.name))); }
+        };
+        if !arg_counts.contains(&function.args.len()) {
+            return Err(DbError::Execution(format!("{} expects {} argument(s), got {}", function_name, arg_counts[0], function.args.len())));
+        }
+        Ok(Self {
+            function,
+            distinct_values: if function.distinct { Some(HashSet::new()) } else { None },
+            state,
+        })
+    }
+
+    /// Adds the row `row` to the aggregate. NULL inputs are ignored, except by COUNT(*).
+    pub fn update(&mut self, row: &dyn ColumnLookup) -> Result<(), DbError> {
+        if let Some(filter) = &self.function.filter {
+            if !eval_predicate(filter, row)? {
+                return Ok(());
+            }
+        }
+        let value = match &self.function.args[0] {
+            FunctionArg::Unnamed(FunctionArgExpr::Wildcard) if matches!(self.state, AggregateState::Count(_)) => Value::Bool(true),
+            FunctionArg::Unnamed(FunctionArgExpr::Expr(expr)) | FunctionArg::Named { arg: FunctionArgExpr::Expr(expr), .. } => eval_expr(expr, row)?,
+            arg => { return Err(DbError::Unsupported(format!("argument {} for {}", arg, self.function.name))); }
+        };
+        if value.is_null() {
+            return Ok(());
+        }
+        if let Some(distinct_values) = &mut self.distinct_values {
+            if !distinct_values.insert(value.clone()) {
+                return Ok(());
+            }
+        }
+        let function_name = &self.function.name;
+        let add = |total: &Option<Value>, value: Value| -> Result<Option<Value>, DbError> {
+            if !value.is_numeric() {
+                return Err(DbError::Execution(format!("{} expects numbers, got {}", function_name, value)));
+            }
+            match total {
+                Some(total) => eval_binary_op(total, &BinaryOperator::Plus, &value).map(Some),
+                None => Ok(Some(value)),
+            }
+        };
+        match &mut self.state {
+            AggregateState::Count(count) => *count += 1,
+            AggregateState::Sum(total) => *total = add(total, value)?,
+            AggregateState::Avg(total, count) => {
+                *total = add(total, value)?;
+                *count += 1;
+            },
+            AggregateState::Min(min) => {
+                if min.as_ref().is_none_or(|min| value < *min) {
+                    *min = Some(value);
+                }
+            },
+            AggregateState::Max(max) => {
+                if max.as_ref().is_none_or(|max| value > *max) {
+                    *max = Some(value);
+                }
+            },
+            AggregateState::StringAgg(parts) => {
+                let separator = match self.function.args.get(1) {
+                    Some(FunctionArg::Unnamed(FunctionArgExpr::Expr(expr))) => eval_expr(expr, row)?,
+                    Some(arg) => { return Err(DbError::Unsupported(format!("argument {} for {}", arg, self.function.name))); }
+                    None => Value::Text(",".to_string()),
+                };
+                let order_values = self.function.order_by.iter().map(|order_by| eval_expr(&order_by.expr, row)).collect::<Result<Vec<_>, _>>()?;
+                let separator = if separator.is_null() { String::new() } else { separator.to_string() };
+                parts.push((order_values, separator, value.to_string()));
+            },
+        }
+        Ok(())
+    }
+
+    /// Value of the aggregate over every row added so far, NULL for an empty group except with COUNT.
+    /// More rows can still be added, as a window frame growing row by row does.
+    pub fn finish(&self) -> Value {
+        match &self.state {
+            AggregateState::Count(count) => Value::Int64(*count),
+            AggregateState::Sum(total) | AggregateState::Min(total) | AggregateState::Max(total) => total.clone().unwrap_or(Value::Null),
+            AggregateState::Avg(total, count) => match total.as_ref().and_then(|total| total.as_f64()) {
+                Some(total) => Value::Float64(total / *count as f64),
+                None => Value::Null,
+            },
+            AggregateState::StringAgg(parts) => {
+                if parts.is_empty() {
+                    return Value::Null;
+                }
+                let mut parts = parts.clone();
+                let order_by = &self.function.order_by;
+                parts.sort_by(|(l_values, _, _), (r_values, _, _)| {
+                    for ((l_value, r_value), order_by) in l_values.iter().zip(r_values).zip(order_by) {
+                        let ordering = if order_by.asc == Some(false) { r_value.cmp(l_value) } else { l_value.cmp(r_value) };
+                        if ordering.is_ne() {
+                            return ordering;
+                        }
+                    }
+                    std::cmp::Ordering::Equal
+                });
+                let mut text = String::new();
+                for (position, (_, separator, part)) in parts.iter().enumerate() {
+                    if position > 0 {
+                        text.push_str(separator);
+                    }
+                    text.push_str(part);
+                }
+                Value::Text(text)
+            },
+        }
+    }
+}
@@ -1,11 +1,16 @@
-use std::collections::HashMap;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::ops::Bound;
+use std::rc::Rc;
 use std::sync::{Arc, Mutex};
-use sqlparser::ast::{ColumnDef, Expr, DataType, ReferentialAction, Value};
-use crate::validators::validate_value_for_col;
-use crate::raw_inner_value::InnerRawValue;
-use std::ops::Deref;
-use buffers_unsafe_copy::vector::unsafe_copy;
-use buffers_unsafe_copy::string::{unsafe_copy as unsafe_copy_str, unsafe_copy_option_str};
+use sqlparser::ast::{Expr, DataType, Query, ReferentialAction, Value as SqlValue};
+use crate::validators::{assign_value, validate_value_for_col};
+use crate::error::DbError;
+use crate::executor::ResultSet;
+use crate::value::Value;
+use crate::expression::{ColumnLookup, EmptyRow, eval_expr, eval_predicate};
+use crate::index::{ColumnBound, IndexKind, SecondaryIndex, column_bounds};
+use buffers_unsafe_copy::string::unsafe_copy as unsafe_copy_str;
 
 pub enum SelectFields {
     WildCard,
@@ -36,28 +41,121 @@ pub struct ColumnInfo {
 }
 
 impl ColumnInfo {
-    pub fn validate_value(&self, value: &Value)-> bool {
-        let is_nullable = self.properties.iter().find(|&prop| {
-            match prop {
-                ColumnProperties::Null => true,
-                ColumnProperties::AutoIncrement => true,
-                ColumnProperties::Default(_expr)=> true,
-                _=> false
-            }
-        }).is_some();
-        validate_value_for_col(&self.type_name, value, is_nullable)
+    /// Coerces `value` for the column `c_name` described by `self`, rejecting values that do not fit.
+    pub fn validate_value(&self, c_name: &str, value: &SqlValue)-> Result<Value, DbError> {
+        let is_nullable = self.is_nullable();
+        if let (SqlValue::Null, false) = (value, is_nullable) {
+            return Err(DbError::ConstraintViolation {
+                constraint: format!("NOT NULL {}", c_name),
+                message: format!("column {} does not accept NULL", c_name),
+            });
+        }
+        validate_value_for_col(&self.type_name, value, is_nullable).map_err(|reason| DbError::TypeMismatch {
+            column: c_name.to_string(),
+            data_type: self.type_name.to_string(),
+            value: value.to_string(),
+            reason,
+        })
+    }
+
+    /// Converts the computed `value` to the type of the column `c_name` described by `self`. Unlike `validate_value`,
+    /// which checks what the user typed, numbers are rounded to the column the way CAST rounds them.
+    pub fn convert_value(&self, c_name: &str, value: &Value) -> Result<Value, DbError> {
+        if value.is_null() {
+            return self.validate_value(c_name, &SqlValue::Null);
+        }
+        assign_value(value, &self.type_name).map_err(|reason| DbError::TypeMismatch {
+            column: c_name.to_string(),
+            data_type: self.type_name.to_string(),
+            value: value.to_string(),
+            reason,
+        })
+    }
+
+    /// Columns accept NULL unless declared NOT NULL or part of the primary key.
+    pub fn is_nullable(&self) -> bool {
+        !self.properties.iter().any(|prop| matches!(prop, ColumnProperties::NotNull | ColumnProperties::PriamryKey))
+    }
+
+    pub fn is_auto_increment(&self) -> bool {
+        self.properties.iter().any(|prop| matches!(prop, ColumnProperties::AutoIncrement))
+    }
+
+    pub fn default_expr(&self) -> Option<&Expr> {
+        self.properties.iter().find_map(|prop| match prop {
+            ColumnProperties::Default(expr) => Some(expr),
+            _ => None,
+        })
     }
 }
 
+/// UNIQUE constraint over one or more columns, either declared on a column or at table level.
+#[derive(Debug)]
+pub struct UniqueConstraint {
+    pub name: String,
+    pub columns: Vec<String>,
+}
+
+/// Order requested on one column of a scan.
+#[derive(Debug, Clone)]
+pub struct SortColumn {
+    pub name: String,
+    pub descending: bool,
+    pub nulls_first: bool,
+}
+
+/// Key of a row in its table: the primary key values, or a generated row id for tables without a primary key.
+pub type RowKey = Vec<Value>;
+
 #[derive(Debug)]
 pub struct TableRowData {
-    column_data: HashMap<String, Option<String>>,
+    column_data: Vec<Value>,//values in the declared column order of the table
+}
+
+/// A stored row seen through its table's column definitions, used to evaluate expressions.
+pub struct RowScope<'a> {
+    column_positions: &'a HashMap<String, usize>,
+    row: &'a TableRowData,
+}
+
+impl RowScope<'_> {
+    pub fn values(&self) -> &[Value] {
+        &self.row.column_data
+    }
+}
+
+impl ColumnLookup for RowScope<'_> {
+    fn lookup(&self, _qualifier: Option<&str>, name: &str) -> Result<Value, DbError> {
+        match self.column_positions.get(name) {
+            Some(position) => Ok(self.row.column_data[*position].clone()),
+            None => Err(DbError::UnknownColumn(name.to_string())),
+        }
+    }
+}
+
+/// A stored row of the table an UPDATE or DELETE changes, whose subqueries run in `context`.
+struct StatementRow<'a> {
+    row: &'a RowScope<'a>,
+    context: &'a dyn ColumnLookup,
 }
 
+impl ColumnLookup for StatementRow<'_> {
+    fn lookup(&self, qualifier: Option<&str>, name: &str) -> Result<Value, DbError> {
+        self.row.lookup(qualifier, name)
+    }
+
+    fn run_subquery(&self, query: &Query, row: &dyn ColumnLookup) -> Result<Rc<ResultSet>, DbError> {
+        self.context.run_subquery(query, row)
+    }
+}
+
+type StoredRows<'a> = Box<dyn Iterator<Item = (&'a RowKey, &'a Arc<TableRowData>)> + 'a>;
+type KeyedRows<'a> = Vec<(&'a RowKey, RowScope<'a>)>;
+
 #[derive(Debug)]
 pub struct TableData {
-    row_data: HashMap<String, Arc<TableRowData>>,//HashMap<"pk", {..data}>/HashMap<"pk1_pk2", {..data}>
-    indexed_data: HashMap<String, Arc<TableRowData>>,
+    row_data: HashMap<RowKey, Arc<TableRowData>>,//HashMap<[pk], {..data}>/HashMap<[pk1, pk2], {..data}>
+    indexed_data: HashMap<String, SecondaryIndex>,//HashMap<"index name", index>
 }
 
 #[derive(Debug)]
@@ -65,7 +163,12 @@ pub struct TableInfo {
     table_name: String,
     primary_keys: Vec<String>,
     indexes: Vec<String>,
+    unique_constraints: Vec<UniqueConstraint>,
+    columns: Vec<String>,//declared order
+    column_positions: HashMap<String, usize>,
     column_properies: HashMap<String, ColumnInfo>,
+    auto_increment_value: i64,//last value assigned to an AUTO_INCREMENT column
+    last_row_id: u64,//last row id generated for a table without primary key
     data: TableData,
 }
 
@@ -74,18 +177,30 @@ pub struct DataBase {
     tables: HashMap<String, Arc<Mutex<TableInfo>>>,
 }
 
+impl Default for TableRowData {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl TableRowData {
     pub fn new() -> Self {
         Self {
-            column_data: HashMap::new(),
+            column_data: Vec::new(),
         }
     }
-    pub fn get_wild_card(&self) -> Vec<&Option<String>> {
-        self.column_data.values().collect()
+    pub fn get_wild_card(&self) -> Vec<&Value> {
+        self.column_data.iter().collect()
     }
 
-    pub fn get_named_fields(&self, fields: &Vec<String>) -> Vec<&Option<String>> {
-        fields.iter().map(|field_name| self.column_data.get(field_name).unwrap()).collect()
+    pub fn get_named_fields(&self, fields: &[String], column_positions: &HashMap<String, usize>) -> Vec<&Value> {
+        fields.iter().map(|field_name| &self.column_data[*column_positions.get(field_name).unwrap()]).collect()
+    }
+}
+
+impl Default for TableData {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -97,13 +212,30 @@ impl TableData {
         }
     }
 
-    pub fn find_all(&self, selection:SelectFields) -> Vec<Vec<&Option<String>>>{
+    /// Stores a row under `row_key` and adds it to every secondary index.
+    pub fn insert_row(&mut self, row_key: RowKey, row: TableRowData) {
+        for index in self.indexed_data.values_mut() {
+            index.insert(&row_key, &row.column_data);
+        }
+        self.row_data.insert(row_key, Arc::new(row));
+    }
+
+    /// Removes a row and every secondary index entry pointing to it.
+    pub fn remove_row(&mut self, row_key: &RowKey) -> Option<Arc<TableRowData>> {
+        let row = self.row_data.remove(row_key)?;
+        for index in self.indexed_data.values_mut() {
+            index.remove(row_key, &row.column_data);
+        }
+        Some(row)
+    }
+
+    pub fn find_all(&self, selection:SelectFields, column_positions: &HashMap<String, usize>) -> Vec<Vec<&Value>>{
         match selection {
             SelectFields::WildCard => {
                 self.row_data.values().map(|val_ref| val_ref.get_wild_card()).collect()
             },
             SelectFields::NamedFields(fields) => {
-                self.row_data.values().map(|val_ref| val_ref.get_named_fields(&fields)).collect()
+                self.row_data.values().map(|val_ref| val_ref.get_named_fields(&fields, column_positions)).collect()
             }
         }
     }
@@ -114,67 +246,523 @@ impl TableInfo {
         table_name: String,
         primary_keys: Vec<String>,
         indexes: Vec<String>,
-        column_properies: HashMap<String, ColumnInfo>
+        unique_constraints: Vec<UniqueConstraint>,
+        column_properies: Vec<(String, ColumnInfo)>
     ) -> Self {
+        let columns: Vec<String> = column_properies.iter().map(|(c_name, _)| c_name.clone()).collect();
+        let column_positions: HashMap<String, usize> = columns.iter().enumerate().map(|(position, c_name)| (c_name.clone(), position)).collect();
+
+        // every UNIQUE constraint is enforced through an ordered index, other key columns get one of their own
+        let mut data = TableData::new();
+        let mut index_definitions: Vec<(String, Vec<String>, bool)> = unique_constraints.iter()
+            .map(|constraint| (constraint.name.clone(), constraint.columns.clone(), true))
+            .collect();
+        for c_name in &indexes {
+            if !index_definitions.iter().any(|(_, index_columns, _)| index_columns.first() == Some(c_name)) {
+                index_definitions.push((key_index_name(&table_name, c_name), vec![c_name.clone()], false));
+            }
+        }
+        for (name, index_columns, unique) in index_definitions {
+            let positions = index_columns.iter().map(|c_name| column_positions[c_name]).collect();
+            data.indexed_data.insert(name.clone(), SecondaryIndex::new(name, index_columns, positions, IndexKind::BTree, unique));
+        }
 
         Self {
             table_name,
             primary_keys,
             indexes,
-            column_properies,
-            data: TableData::new(),
+            unique_constraints,
+            columns,
+            column_positions,
+            column_properies: column_properies.into_iter().collect(),
+            auto_increment_value: 0,
+            last_row_id: 0,
+            data,
         }
     }
 
-    pub fn insert_values(&mut self, columns: &Vec<String>, values: Vec<Vec<Expr>>) -> Result<(), &'static str>{
-        //let columns_with_prop:  Vec<(String, ColumnInfo)> = Vec::new();
-        if columns.len() == 0 || columns.len() > self.column_properies.len() {
-            return Err("wrong number of columns");
-        }
-        let mut found = 0;
-        for (c_name, c_props) in &self.column_properies {
-            if let Some (_) = columns.iter().find(|&c_name_i| c_name_i.eq(c_name)) {
-                found += 1;
-                //columns_with_prop.push((c_name, c_props));
-            }
+    pub fn insert_values(&mut self, columns: &[String], values: Vec<Vec<Expr>>) -> Result<(), DbError>{
+        // literals are checked as typed, computed values converted to the column type
+        self.insert_rows(columns, values, |col_prop, c_name, value_expr| match value_expr {
+            Expr::Value(SqlValue::Null) => Ok(Value::Null),
+            Expr::Value(value) => col_prop.validate_value(c_name, &value),
+            expr => match eval_expr(&expr, &EmptyRow)? {
+                Value::Null => Ok(Value::Null),
+                value => col_prop.convert_value(c_name, &value),
+            },
+        })
+    }
+
+    /// Inserts the rows of an INSERT ... SELECT, each value converted to the type of its column.
+    pub fn insert_query_rows(&mut self, columns: &[String], rows: Vec<Vec<Value>>) -> Result<(), DbError> {
+        self.insert_rows(columns, rows, |col_prop, c_name, value| match value {
+            Value::Null => Ok(Value::Null),
+            value => col_prop.convert_value(c_name, &value),
+        })
+    }
+
+    /// Inserts `rows` of values for `columns`, `typed` giving the stored form of each; a NULL is checked
+    /// against the column, or replaced by the next AUTO_INCREMENT value.
+    fn insert_rows<T>(&mut self, columns: &[String], rows: Vec<Vec<T>>, typed: impl Fn(&ColumnInfo, &str, T) -> Result<Value, DbError>) -> Result<(), DbError> {
+        if columns.is_empty() || columns.len() > self.column_properies.len() {
+            return Err(DbError::Execution("wrong number of columns".to_string()));
         }
-        if columns.len() != found {
-            return Err("the colummn list does not exist for table");
+        if let Some(c_name) = columns.iter().find(|c_name| !self.column_properies.contains_key(*c_name)) {
+            return Err(DbError::UnknownColumn(c_name.clone()));
         }
-        for val_row in values {
+        // validate every row before storing any, so that a rejected value rejects the whole statement
+        let mut new_rows = Vec::with_capacity(rows.len());
+        let mut auto_increment_value = self.auto_increment_value;
+        for val_row in rows {
             if columns.len() != val_row.len() {
-                return Err("wrong number of columns");
+                return Err(DbError::Execution("wrong number of columns".to_string()));
             }
-            let mut pk_string = "".to_string();
             let mut row_data = TableRowData::new();
-            for (c_name, value_expr) in columns.iter().zip(val_row.iter()) {
+            row_data.column_data.resize(self.columns.len(), Value::Null);
+            let mut provided = vec![false; self.columns.len()];
+            for (c_name, value) in columns.iter().zip(val_row) {
                 let col_prop = self.column_properies.get(c_name).unwrap();
-                if let Expr::Value(value) = value_expr {
-                    let value_raw = value.get_inner_raw_str();
-                    if self.primary_keys.contains(c_name){
-                        if pk_string.len()>0 {
-                            pk_string.push_str("_");
-                        }
-                        if let Some(v) = &value_raw {
-                            pk_string.push_str(v);
-                        }else {
-                            pk_string.push_str("|");
-                        }
+                let typed_value = match typed(col_prop, c_name, value)? {
+                    Value::Null if col_prop.is_auto_increment() => continue,
+                    Value::Null => col_prop.validate_value(c_name, &SqlValue::Null)?,
+                    typed_value => typed_value,
+                };
+                match typed_value {
+                    Value::Int64(v) if col_prop.is_auto_increment() => auto_increment_value = auto_increment_value.max(v),
+                    Value::UInt64(v) if col_prop.is_auto_increment() => auto_increment_value = auto_increment_value.max(v.min(i64::MAX as u64) as i64),
+                    _ => {}
+                }
+                row_data.column_data[self.column_positions[c_name]] = typed_value;
+                provided[self.column_positions[c_name]] = true;
+            }
+            for (position, c_name) in self.columns.iter().enumerate() {
+                if !provided[position] {
+                    row_data.column_data[position] = self.omitted_value(c_name, &mut auto_increment_value)?;
+                }
+            }
+            new_rows.push(row_data);
+        }
+        self.check_unique(&new_rows, &HashSet::new())?;
+        self.auto_increment_value = auto_increment_value;
+        for row_data in new_rows {
+            let row_key = self.new_row_key(&row_data);
+            self.data.insert_row(row_key, row_data);
+        }
+        Ok(())
+    }
+
+    /// Value stored for a column left out of an INSERT: the next AUTO_INCREMENT value, the DEFAULT, or NULL.
+    fn omitted_value(&self, c_name: &str, auto_increment_value: &mut i64) -> Result<Value, DbError> {
+        let col_prop = &self.column_properies[c_name];
+        let value = if col_prop.is_auto_increment() {
+            *auto_increment_value += 1;
+            Value::Int64(*auto_increment_value)
+        } else {
+            self.default_value(c_name)?
+        };
+        col_prop.convert_value(c_name, &value)
+    }
+
+    /// Primary key values of `row_data`, None when the table has no primary key.
+    fn primary_key_of(&self, row_data: &TableRowData) -> Option<RowKey> {
+        if self.primary_keys.is_empty() {
+            return None;
+        }
+        Some(self.primary_keys.iter().map(|pk_name| row_data.column_data[self.column_positions[pk_name]].clone()).collect())
+    }
+
+    /// Key under which a newly inserted row is stored.
+    fn new_row_key(&mut self, row_data: &TableRowData) -> RowKey {
+        self.primary_key_of(row_data).unwrap_or_else(|| {
+            self.last_row_id += 1;
+            vec![Value::UInt64(self.last_row_id)]
+        })
+    }
+
+    /// Rejects `new_rows` if they repeat a primary key or UNIQUE value, among themselves or with the
+    /// stored rows. Rows stored under `replaced_keys` are about to be overwritten and do not count.
+    fn check_unique(&self, new_rows: &[TableRowData], replaced_keys: &HashSet<RowKey>) -> Result<(), DbError> {
+        if !self.primary_keys.is_empty() {
+            let mut seen_keys: HashSet<RowKey> = HashSet::new();
+            for row in new_rows {
+                let row_key = self.primary_key_of(row).unwrap();
+                let is_stored = self.data.row_data.contains_key(&row_key) && !replaced_keys.contains(&row_key);
+                if is_stored || !seen_keys.insert(row_key.clone()) {
+                    return Err(self.duplicate_key_error(&format!("PRIMARY KEY ({})", self.primary_keys.join(", ")), &row_key));
+                }
+            }
+        }
+        for index in self.data.indexed_data.values().filter(|index| index.is_unique()) {
+            let mut seen_values: HashSet<Vec<Value>> = HashSet::new();
+            for row in new_rows {
+                let values = index.key_of(&row.column_data);
+                // NULL never equals anything, so tuples containing NULL cannot conflict
+                if values.iter().any(Value::is_null) {
+                    continue;
+                }
+                let is_stored = index.get(&values).iter().any(|row_key| !replaced_keys.contains(row_key));
+                if is_stored || !seen_values.insert(values.clone()) {
+                    return Err(self.duplicate_key_error(index.name(), &values));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn duplicate_key_error(&self, constraint: &str, values: &[Value]) -> DbError {
+        let values: Vec<String> = values.iter().map(|value| value.to_string()).collect();
+        DbError::ConstraintViolation {
+            constraint: constraint.to_string(),
+            message: format!("duplicate value ({}) in table {}", values.join(", "), self.table_name),
+        }
+    }
+
+    /// Applies `assignments` to every row matching `predicate`, returns the number of updated rows.
+    /// `context` runs the subqueries of the assignments and of `predicate`.
+    pub fn update_values(&mut self, assignments: &[(String, Expr)], predicate: Option<&Expr>, context: &dyn ColumnLookup) -> Result<usize, DbError> {
+        let mut assigned_positions = Vec::with_capacity(assignments.len());
+        for (c_name, _) in assignments {
+            match self.column_positions.get(c_name) {
+                Some(position) => assigned_positions.push(*position),
+                None => return Err(DbError::UnknownColumn(c_name.clone())),
+            }
+        }
+        let on_update: Vec<(usize, &Expr)> = self.columns.iter().enumerate()
+            .filter(|(position, _)| !assigned_positions.contains(position))
+            .filter_map(|(position, c_name)| {
+                self.column_properies[c_name].properties.iter().find_map(|prop| match prop {
+                    ColumnProperties::OnUpdate(expr) => Some((position, expr)),
+                    _ => None,
+                })
+            })
+            .collect();
+
+        // compute every new row first so that a failing assignment leaves the table untouched
+        let mut updated_rows: Vec<(RowKey, TableRowData)> = Vec::new();
+        for (row_key, scope) in self.matching_rows(predicate, &[], None, context)?.0 {
+            let mut new_row = TableRowData { column_data: scope.row.column_data.clone() };
+            let new_values = assignments.iter().map(|(_, expr)| expr)
+                .zip(assigned_positions.iter())
+                .chain(on_update.iter().map(|(position, expr)| (*expr, position)));
+            for (expr, position) in new_values {
+                let c_name = &self.columns[*position];
+                let value = eval_expr(expr, &StatementRow { row: &scope, context })?;
+                new_row.column_data[*position] = self.column_properies[c_name].convert_value(c_name, &value)?;
+            }
+            updated_rows.push((row_key.clone(), new_row));
+        }
+
+        let updated_count = updated_rows.len();
+        let (replaced_keys, new_rows): (Vec<RowKey>, Vec<TableRowData>) = updated_rows.into_iter().unzip();
+        let replaced_key_set: HashSet<RowKey> = replaced_keys.iter().cloned().collect();
+        self.check_unique(&new_rows, &replaced_key_set)?;
+        self.replace_rows(replaced_keys, new_rows);
+        Ok(updated_count)
+    }
+
+    /// Stores `new_rows` in place of the rows under `replaced_keys`, re-keying them if needed.
+    pub fn replace_rows(&mut self, replaced_keys: Vec<RowKey>, new_rows: Vec<TableRowData>) {
+        for old_row_key in &replaced_keys {
+            self.data.remove_row(old_row_key);
+        }
+        for (old_row_key, new_row) in replaced_keys.into_iter().zip(new_rows) {
+            let row_key = self.primary_key_of(&new_row).unwrap_or(old_row_key);
+            self.data.insert_row(row_key, new_row);
+        }
+    }
+    pub fn find_all(&self, selection:SelectFields) -> Vec<Vec<&Value>> {
+        self.data.find_all(selection, &self.column_positions)
+    }
+
+    /// Rows for which `predicate` evaluates to TRUE, every row without a predicate.
+    pub fn scan(&self, predicate: Option<&Expr>) -> Result<Vec<RowScope<'_>>, DbError> {
+        Ok(self.matching_rows(predicate, &[], None, &EmptyRow)?.0.into_iter().map(|(_, scope)| scope).collect())
+    }
+
+    /// Like `scan`, also returns whether the rows already follow `order`, which is the case when
+    /// they are read from an ordered index instead of looked up through one.
+    /// The scan stops after `limit` rows when they are unordered or already follow `order`.
+    pub fn scan_ordered(&self, predicate: Option<&Expr>, order: &[SortColumn], limit: Option<usize>) -> Result<(Vec<RowScope<'_>>, bool), DbError> {
+        let (rows, ordered) = self.matching_rows(predicate, order, limit, &EmptyRow)?;
+        Ok((rows.into_iter().map(|(_, scope)| scope).collect(), ordered))
+    }
+
+    /// Keys of the rows matching `predicate`, of every row without a predicate. `context` runs the subqueries of `predicate`.
+    pub fn matching_keys(&self, predicate: Option<&Expr>, context: &dyn ColumnLookup) -> Result<Vec<RowKey>, DbError> {
+        Ok(self.matching_rows(predicate, &[], None, context)?.0.into_iter().map(|(row_key, _)| row_key.clone()).collect())
+    }
+
+    fn matching_rows(&self, predicate: Option<&Expr>, order: &[SortColumn], limit: Option<usize>, context: &dyn ColumnLookup) -> Result<(KeyedRows<'_>, bool), DbError> {
+        let mut ret = Vec::new();
+        let (candidates, ordered) = self.candidate_rows(predicate, order);
+        let limit = limit.filter(|_| order.is_empty() || ordered);
+        for (row_key, row) in candidates {
+            if limit.is_some_and(|limit| ret.len() >= limit) {
+                break;
+            }
+            let scope = RowScope { column_positions: &self.column_positions, row };
+            if let Some(predicate) = predicate {
+                if !eval_predicate(predicate, &StatementRow { row: &scope, context })? {
+                    continue;
+                }
+            }
+            ret.push((row_key, scope));
+        }
+        Ok((ret, ordered))
+    }
+
+    /// Rows that may satisfy `predicate`: the ones found through an index when a conjunct allows it, all rows otherwise.
+    /// Without such a lookup, the rows are read in `order` from an ordered index if one provides it, the flag telling so.
+    fn candidate_rows(&self, predicate: Option<&Expr>, order: &[SortColumn]) -> (StoredRows<'_>, bool) {
+        if let Some(row_keys) = predicate.and_then(|predicate| self.indexed_keys(predicate)) {
+            return (Box::new(row_keys.into_iter().filter_map(|row_key| self.data.row_data.get_key_value(&row_key))), false);
+        }
+        match self.ordering_index(order) {
+            Some(index) => {
+                let row_keys = index.ordered_keys(order[0].descending).unwrap();
+                (Box::new(row_keys.filter_map(|row_key| self.data.row_data.get_key_value(row_key))), true)
+            },
+            None => (Box::new(self.data.row_data.iter()), false),
+        }
+    }
+
+    /// Ordered index whose leading columns are the columns of `order`, iterated in a single direction.
+    /// An ordered index keeps NULL first, so nullable columns must ask for NULLS FIRST ascending or NULLS LAST descending.
+    fn ordering_index(&self, order: &[SortColumn]) -> Option<&SecondaryIndex> {
+        let descending = order.first()?.descending;
+        let follows_index = |column: &SortColumn| {
+            column.descending == descending
+                && (column.nulls_first != descending || self.column_properies.get(&column.name).is_some_and(|col_prop| !col_prop.is_nullable()))
+        };
+        if !order.iter().all(follows_index) {
+            return None;
+        }
+        self.data.indexed_data.values()
+            .filter(|index| index.kind() == IndexKind::BTree && index.columns().len() >= order.len())
+            .find(|index| index.columns().iter().zip(order).all(|(c_name, column)| *c_name == column.name))
+    }
+
+    /// Keys of the rows the primary key or an index finds for the conjuncts of `predicate`, None when neither applies.
+    /// The keys are a superset of the matching rows, the predicate still has to be checked on each.
+    fn indexed_keys(&self, predicate: &Expr) -> Option<Vec<RowKey>> {
+        let mut pinned: HashMap<String, Vec<Value>> = HashMap::new();
+        let mut ranges: Vec<(String, Bound<Value>, Bound<Value>)> = Vec::new();
+        for (c_name, bound) in column_bounds(predicate) {
+            match bound {
+                ColumnBound::OneOf(values) => {
+                    let values: Option<Vec<Value>> = values.iter().map(|value| self.stored_form(&c_name, value)).collect();
+                    if let Some(values) = values {
+                        pinned.entry(c_name).or_insert(values);
                     }
-                    if col_prop.validate_value(value){
-                        row_data.column_data.insert(unsafe_copy_str(c_name), value_raw);
+                },
+                ColumnBound::Range(lower, upper) => {
+                    let stored_bound = |bound: Bound<Value>| match bound {
+                        Bound::Included(value) => self.stored_form(&c_name, &value).map(Bound::Included),
+                        Bound::Excluded(value) => self.stored_form(&c_name, &value).map(Bound::Excluded),
+                        Bound::Unbounded => Some(Bound::Unbounded),
+                    };
+                    if let (Some(lower), Some(upper)) = (stored_bound(lower), stored_bound(upper)) {
+                        ranges.push((c_name, lower, upper));
                     }
-                }else {
-                    return Err("only raw values are supported for insert");
+                },
+            }
+        }
+
+        let mut indexes: Vec<&SecondaryIndex> = self.data.indexed_data.values().collect();
+        indexes.sort_by(|l, r| r.columns().len().cmp(&l.columns().len()).then_with(|| l.name().cmp(r.name())));
+        let all_pinned = |columns: &[String]| columns.iter().all(|c_name| pinned.contains_key(c_name));
+        let mut row_keys = None;
+        // prefer point lookups on the primary key, then exact lookups on every indexed column,
+        // then the leading column of an ordered index
+        if !self.primary_keys.is_empty() && all_pinned(&self.primary_keys) {
+            row_keys = Some(pinned_tuples(&self.primary_keys, &pinned));
+        } else if let Some(index) = indexes.iter().find(|index| all_pinned(index.columns())) {
+            row_keys = Some(pinned_tuples(index.columns(), &pinned).iter().flat_map(|key| index.get(key).iter().cloned()).collect::<Vec<RowKey>>());
+        } else if let Some(index) = indexes.iter().find(|index| index.kind() == IndexKind::BTree && pinned.contains_key(&index.columns()[0])) {
+            let mut keys = Vec::new();
+            for value in &pinned[&index.columns()[0]] {
+                keys.extend(index.range(Bound::Included(value), Bound::Included(value))?);
+            }
+            row_keys = Some(keys);
+        } else {
+            for (c_name, lower, upper) in &ranges {
+                if let Some(index) = indexes.iter().find(|index| index.kind() == IndexKind::BTree && &index.columns()[0] == c_name) {
+                    row_keys = index.range(lower.as_ref(), upper.as_ref());
+                    break;
                 }
+            }
+        }
+        // IN lists may repeat values
+        row_keys.map(|row_keys| {
+            let mut seen: HashSet<RowKey> = HashSet::new();
+            row_keys.into_iter().filter(|row_key| seen.insert(row_key.clone())).collect()
+        })
+    }
+
+    /// `value` converted to the type stored in column `c_name`, None when the conversion would change how it compares.
+    fn stored_form(&self, c_name: &str, value: &Value) -> Option<Value> {
+        let col_prop = self.column_properies.get(c_name)?;
+        col_prop.convert_value(c_name, value).ok()
+            .filter(|stored| stored.compare(value) == Some(Ordering::Equal))
+    }
+
+    /// Values of column `c_name` for the rows stored under `row_keys`.
+    pub fn values_for_keys(&self, row_keys: &[RowKey], c_name: &str) -> Result<Vec<Value>, DbError> {
+        let position = *self.column_positions.get(c_name).ok_or_else(|| DbError::UnknownColumn(c_name.to_string()))?;
+        Ok(row_keys.iter()
+            .filter_map(|row_key| self.data.row_data.get(row_key))
+            .map(|row| row.column_data[position].clone())
+            .collect())
+    }
+
+    /// Keys of the rows whose column `c_name` equals one of `values`.
+    pub fn keys_with_values(&self, c_name: &str, values: &[Value]) -> Result<Vec<RowKey>, DbError> {
+        let position = *self.column_positions.get(c_name).ok_or_else(|| DbError::UnknownColumn(c_name.to_string()))?;
+        let value_set: HashSet<&Value> = values.iter().filter(|v| !v.is_null()).collect();
+        Ok(self.data.row_data.iter()
+            .filter(|(_, row)| value_set.contains(&row.column_data[position]))
+            .map(|(row_key, _)| row_key.clone())
+            .collect())
+    }
+
+    /// Foreign keys of this table referring to `table_name`: (column, referred column, ON DELETE action).
+    pub fn foreign_keys_to(&self, table_name: &str) -> Vec<(String, Option<String>, Option<ReferentialAction>)> {
+        let mut ret = Vec::new();
+        for c_name in &self.columns {
+            for prop in &self.column_properies[c_name].properties {
+                if let ColumnProperties::ForeignKey { foreign_table, referred_columns, on_delete, .. } = prop {
+                    if foreign_table == table_name {
+                        ret.push((c_name.clone(), referred_columns.first().cloned(), *on_delete));
+                    }
+                }
+            }
+        }
+        ret
+    }
+
+    /// Value of the DEFAULT expression of `c_name`, NULL when the column has none.
+    pub fn default_value(&self, c_name: &str) -> Result<Value, DbError> {
+        let col_prop = self.column_properies.get(c_name).ok_or_else(|| DbError::UnknownColumn(c_name.to_string()))?;
+        match col_prop.default_expr() {
+            Some(expr) => eval_expr(expr, &EmptyRow),
+            None => Ok(Value::Null),
+        }
+    }
+
+    /// Validates the values an ON DELETE action sets, as (row key, column, value), and checks the changed rows
+    /// against the keys of the table. Rows under `removed_keys` are about to be deleted: they are left out and
+    /// do not count as duplicates. Returns the keys of the changed rows and their new contents.
+    pub fn prepare_set_values(&self, changes: &[(RowKey, String, Value)], removed_keys: &HashSet<RowKey>) -> Result<(Vec<RowKey>, Vec<TableRowData>), DbError> {
+        let mut replaced_keys: Vec<RowKey> = Vec::new();
+        let mut new_rows: Vec<TableRowData> = Vec::new();
+        let mut row_slots: HashMap<&RowKey, usize> = HashMap::new();
+        for (row_key, c_name, value) in changes {
+            let position = *self.column_positions.get(c_name).ok_or_else(|| DbError::UnknownColumn(c_name.to_string()))?;
+            let Some(row) = self.data.row_data.get(row_key).filter(|_| !removed_keys.contains(row_key)) else {
+                continue;
+            };
+            let typed_value = self.column_properies[c_name].convert_value(c_name, value)?;
+            let slot = *row_slots.entry(row_key).or_insert_with(|| {
+                replaced_keys.push(row_key.clone());
+                new_rows.push(TableRowData { column_data: row.column_data.clone() });
+                new_rows.len() - 1
+            });
+            new_rows[slot].column_data[position] = typed_value;
+        }
+        let ignored_keys: HashSet<RowKey> = removed_keys.iter().chain(replaced_keys.iter()).cloned().collect();
+        self.check_unique(&new_rows, &ignored_keys)?;
+        Ok((replaced_keys, new_rows))
+    }
+
+    pub fn delete_keys(&mut self, row_keys: &[RowKey]) -> usize {
+        row_keys.iter().filter(|row_key| self.data.remove_row(row_key).is_some()).count()
+    }
+
+    pub fn primary_keys(&self) -> &[String] {
+        &self.primary_keys
+    }
 
+    /// Column names in the order they were declared by CREATE TABLE.
+    pub fn column_names(&self) -> &[String] {
+        &self.columns
+    }
+
+    pub fn table_name(&self) -> &str {
+        &self.table_name
+    }
+
+    pub fn indexes(&self) -> &[String] {
+        &self.indexes
+    }
+
+    pub fn unique_constraints(&self) -> &[UniqueConstraint] {
+        &self.unique_constraints
+    }
+
+    /// Every secondary index of the table, including the ones backing UNIQUE constraints.
+    pub fn secondary_indexes(&self) -> impl Iterator<Item = &SecondaryIndex> {
+        self.data.indexed_data.values()
+    }
+
+    pub fn has_index(&self, index_name: &str) -> bool {
+        self.data.indexed_data.contains_key(index_name)
+    }
+
+    /// Builds an index over `columns` from the stored rows. A UNIQUE index fails if the rows already repeat a value.
+    pub fn create_index(&mut self, index_name: &str, columns: &[String], kind: IndexKind, unique: bool) -> Result<(), DbError> {
+        if self.has_index(index_name) {
+            return Err(DbError::Execution(format!("Index {} already exists", index_name)));
+        }
+        let mut positions = Vec::with_capacity(columns.len());
+        for c_name in columns {
+            positions.push(*self.column_positions.get(c_name).ok_or_else(|| DbError::UnknownColumn(c_name.clone()))?);
+        }
+        let mut index = SecondaryIndex::new(unsafe_copy_str(index_name), columns.to_vec(), positions, kind, unique);
+        for (row_key, row) in &self.data.row_data {
+            if unique {
+                let values = index.key_of(&row.column_data);
+                if !values.iter().any(Value::is_null) && !index.get(&values).is_empty() {
+                    return Err(self.duplicate_key_error(index_name, &values));
+                }
             }
-            self.data.row_data.insert(pk_string, Arc::new(row_data));
+            index.insert(row_key, &row.column_data);
         }
+        self.data.indexed_data.insert(unsafe_copy_str(index_name), index);
         Ok(())
     }
-    pub fn find_all(&self, selection:SelectFields) -> Vec<Vec<&Option<String>>> {
-        self.data.find_all(selection)
+
+    /// Removes the index `index_name`, returns false when the table has no such index.
+    pub fn drop_index(&mut self, index_name: &str) -> Result<bool, DbError> {
+        if self.unique_constraints.iter().any(|constraint| constraint.name == index_name) {
+            return Err(DbError::Execution(format!("Index {} enforces a UNIQUE constraint of table {} and cannot be dropped", index_name, self.table_name)));
+        }
+        if let Some(c_name) = self.indexes.iter().find(|c_name| key_index_name(&self.table_name, c_name) == index_name) {
+            return Err(DbError::Execution(format!("Index {} backs the key column {} of table {} and cannot be dropped", index_name, c_name, self.table_name)));
+        }
+        Ok(self.data.indexed_data.remove(index_name).is_some())
+    }
+}
+
+/// Name of the index a key column gets implicitly, `<table>_<column>_key`, so that it does not take the
+/// column name away from the indexes users create.
+fn key_index_name(table_name: &str, c_name: &str) -> String {
+    format!("{}_{}_key", table_name, c_name)
+}
+
+/// Every combination of the values `pinned` allows for `columns`, in the order of `columns`.
+fn pinned_tuples(columns: &[String], pinned: &HashMap<String, Vec<Value>>) -> Vec<Vec<Value>> {
+    let mut tuples: Vec<Vec<Value>> = vec![Vec::new()];
+    for c_name in columns {
+        tuples = tuples.into_iter()
+            .flat_map(|tuple| pinned[c_name].iter().map(move |value| [tuple.as_slice(), std::slice::from_ref(value)].concat()))
+            .collect();
+    }
+    tuples
+}
+
+impl Default for DataBase {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -186,20 +774,121 @@ impl DataBase {
     }
     //#[inline]
     pub fn create_table(&mut self,
-        table_name: &String,
+        table_name: &str,
         primary_keys: Vec<String>,
         indexes: Vec<String>,
-        column_properies: HashMap<String, ColumnInfo>
+        unique_constraints: Vec<UniqueConstraint>,
+        column_properies: Vec<(String, ColumnInfo)>
     ) {
-        self.tables.insert(unsafe_copy_str(table_name), Arc::new(Mutex::new(TableInfo::new(unsafe_copy_str(table_name), primary_keys, indexes, column_properies))));
+        self.tables.insert(unsafe_copy_str(table_name), Arc::new(Mutex::new(TableInfo::new(unsafe_copy_str(table_name), primary_keys, indexes, unique_constraints, column_properies))));
     }
 
     //#[inline]
-    pub fn table_exists(&mut self, table_name: &String) -> bool {
+    pub fn table_exists(&mut self, table_name: &str) -> bool {
         self.tables.contains_key(table_name)
     }
 
-    pub fn get_table_ref(&mut self, table_name: &String) -> Option<&Arc<Mutex<TableInfo>>> {
+    pub fn get_table_ref(&self, table_name: &str) -> Option<&Arc<Mutex<TableInfo>>> {
         self.tables.get(table_name)
     }
+
+    /// Whether any table already has an index named `index_name`, index names being shared by the whole database.
+    pub fn index_exists(&self, index_name: &str) -> bool {
+        self.tables.values().any(|table| table.lock().unwrap().has_index(index_name))
+    }
+
+    pub fn create_index(&self, table_name: &str, index_name: &str, columns: &[String], kind: IndexKind, unique: bool) -> Result<(), DbError> {
+        let table = self.get_table_ref(table_name).ok_or_else(|| DbError::UnknownTable(table_name.to_string()))?;
+        if self.index_exists(index_name) {
+            return Err(DbError::Execution(format!("Index {} already exists", index_name)));
+        }
+        table.lock().unwrap().create_index(index_name, columns, kind, unique)
+    }
+
+    /// Drops the index `index_name` from whichever table holds it, returns false when no table does.
+    pub fn drop_index(&self, index_name: &str) -> Result<bool, DbError> {
+        for table in self.tables.values() {
+            if table.lock().unwrap().drop_index(index_name)? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Deletes the rows of `table_name` matching `predicate` and applies the ON DELETE action
+    /// of every foreign key referring to them. Nothing is changed if a RESTRICT/NO ACTION key is violated.
+    /// `context` runs the subqueries of `predicate`.
+    pub fn delete_rows(&self, table_name: &str, predicate: Option<&Expr>, context: &dyn ColumnLookup) -> Result<usize, DbError> {
+        let table = self.get_table_ref(table_name).ok_or_else(|| DbError::UnknownTable(table_name.to_string()))?;
+        let matched_keys = table.lock().unwrap().matching_keys(predicate, context)?;
+
+        let mut deletes: HashMap<String, HashSet<RowKey>> = HashMap::new();
+        let mut set_values: HashMap<String, Vec<(RowKey, String, Value)>> = HashMap::new();//table -> (row key, column, value)
+        deletes.entry(table_name.to_string()).or_default().extend(matched_keys.iter().cloned());
+        let mut pending: Vec<(String, Vec<RowKey>)> = vec![(table_name.to_string(), matched_keys.clone())];
+
+        while let Some((parent_name, parent_keys)) = pending.pop() {
+            for (child_name, child_table) in &self.tables {
+                let foreign_keys = child_table.lock().unwrap().foreign_keys_to(&parent_name);
+                for (c_name, referred_column, on_delete) in foreign_keys {
+                    let parent_values = {
+                        let parent = self.tables[&parent_name].lock().unwrap();
+                        let referred_column = referred_column.or_else(|| parent.primary_keys().first().cloned())
+                            .ok_or_else(|| DbError::Parse(format!("Foreign key {}.{} does not name a referred column", child_name, c_name)))?;
+                        parent.values_for_keys(&parent_keys, &referred_column)?
+                    };
+                    let child = child_table.lock().unwrap();
+                    let already_deleted = deletes.get(child_name);
+                    let child_keys: Vec<RowKey> = child.keys_with_values(&c_name, &parent_values)?
+                        .into_iter()
+                        .filter(|row_key| !already_deleted.is_some_and(|keys| keys.contains(row_key)))
+                        .collect();
+                    if child_keys.is_empty() {
+                        continue;
+                    }
+                    match on_delete {
+                        Some(ReferentialAction::Cascade) => {
+                            deletes.entry(child_name.clone()).or_default().extend(child_keys.iter().cloned());
+                            pending.push((child_name.clone(), child_keys));
+                        },
+                        Some(ReferentialAction::SetNull) => {
+                            set_values.entry(child_name.clone()).or_default()
+                                .extend(child_keys.into_iter().map(|row_key| (row_key, c_name.clone(), Value::Null)));
+                        },
+                        Some(ReferentialAction::SetDefault) => {
+                            let default_value = child.default_value(&c_name)?;
+                            set_values.entry(child_name.clone()).or_default()
+                                .extend(child_keys.into_iter().map(|row_key| (row_key, c_name.clone(), default_value.clone())));
+                        },
+                        Some(ReferentialAction::Restrict) | Some(ReferentialAction::NoAction) | None => {
+                            return Err(DbError::ConstraintViolation {
+                                constraint: format!("FOREIGN KEY {}.{}", child_name, c_name),
+                                message: format!("rows of {} still refer to the deleted rows of {}", child_name, parent_name),
+                            });
+                        },
+                    }
+                }
+            }
+        }
+
+        // every SET NULL/SET DEFAULT value is checked before anything changes
+        let no_keys = HashSet::new();
+        let mut set_rows = Vec::with_capacity(set_values.len());
+        for (child_name, changes) in &set_values {
+            let removed_keys = deletes.get(child_name).unwrap_or(&no_keys);
+            set_rows.push((child_name, self.tables[child_name].lock().unwrap().prepare_set_values(changes, removed_keys)?));
+        }
+        let mut deleted_count = 0;
+        for (delete_table_name, row_keys) in deletes {
+            let row_keys: Vec<RowKey> = row_keys.into_iter().collect();
+            let count = self.tables[&delete_table_name].lock().unwrap().delete_keys(&row_keys);
+            if delete_table_name == table_name {
+                deleted_count = count;
+            }
+        }
+        for (child_name, (replaced_keys, new_rows)) in set_rows {
+            self.tables[child_name].lock().unwrap().replace_rows(replaced_keys, new_rows);
+        }
+        Ok(deleted_count)
+    }
 }
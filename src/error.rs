@@ -0,0 +1,47 @@
+use std::fmt;
+use sqlparser::parser::ParserError;
+
+/// Every failure the engine reports, so that callers can match on the kind of error.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DbError {
+    Parse(String),
+    UnknownTable(String),
+    UnknownColumn(String),
+    TypeMismatch {
+        column: String,
+        data_type: String,
+        value: String,
+        reason: String,
+    },
+    ConstraintViolation {
+        constraint: String,
+        message: String,
+    },
+    Unsupported(String),
+    /// Runtime failure while executing a supported statement, e.g. a division by zero.
+    Execution(String),
+}
+
+impl fmt::Display for DbError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DbError::Parse(message) => write!(f, "parse error: {}", message),
+            DbError::UnknownTable(table_name) => write!(f, "No table named {} exists", table_name),
+            DbError::UnknownColumn(c_name) => write!(f, "No column named {} exists", c_name),
+            DbError::TypeMismatch { column, data_type, value, reason } => {
+                write!(f, "Value {} is not valid for column {} {}: {}", value, column, data_type, reason)
+            },
+            DbError::ConstraintViolation { constraint, message } => write!(f, "constraint {} violated: {}", constraint, message),
+            DbError::Unsupported(feature) => write!(f, "not yet implemented: {}", feature),
+            DbError::Execution(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for DbError {}
+
+impl From<ParserError> for DbError {
+    fn from(error: ParserError) -> Self {
+        DbError::Parse(error.to_string())
+    }
+}
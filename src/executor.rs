@@ -1,21 +1,46 @@
-use crate::data_descriptor::{ ColumnInfo, ColumnProperties, DataBase, SelectFields};
-use sqlparser::ast::{Statement, ObjectName, ColumnOption, SetExpr, TableFactor, SelectItem};
-use std::collections::HashMap;
-use buffers_unsafe_copy::string::{unsafe_copy as unsafe_copy_str, unsafe_copy_option_str};
+use crate::data_descriptor::{ ColumnInfo, ColumnProperties, DataBase, UniqueConstraint};
+use crate::error::DbError;
+use crate::index::IndexKind;
+use crate::query::{execute_query, with_statement_scope};
+use crate::relation::check_qualifiers;
+use crate::value::Value;
+use sqlparser::ast::{Statement, ObjectName, ObjectType, ColumnOption, Expr, FromTable, SetExpr, TableConstraint, TableFactor};
+use buffers_unsafe_copy::string::unsafe_copy as unsafe_copy_str;
+use serde::{Deserialize, Serialize};
+
+/// Rows returned by a query, `columns` naming each position of a row.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ResultSet {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<Value>>,
+}
 
 #[derive(Debug)]
 pub enum SuccessStatus {
     TableCreated(String),
+    IndexCreated(String),
+    IndexDropped(String),
     DataInserted,
+    DataUpdated(usize),
+    DataDeleted(usize),
     DataFetched(Vec<u8>)
 }
 
+/// Name of the table referred to by `name`, ignoring any schema qualifier.
+pub fn table_name_of(name: &ObjectName) -> Result<String, DbError> {
+    let ObjectName(name_idents) = name;
+    match name_idents.last() {
+        Some(ident) => Ok(unsafe_copy_str(&ident.value)),
+        None => Err(DbError::Parse("A valid table name must be provided".to_string())),
+    }
+}
+
 pub trait Execute {
-    fn execute(&self, db: &mut DataBase) -> Result<SuccessStatus, String>;
+    fn execute(&self, db: &mut DataBase) -> Result<SuccessStatus, DbError>;
 }
 
 impl Execute for Statement {
-    fn execute(&self, db: &mut DataBase) -> Result<SuccessStatus, String> {
+    fn execute(&self, db: &mut DataBase) -> Result<SuccessStatus, DbError> {
         match self {
             Statement::CreateTable {
                 name,
@@ -23,12 +48,12 @@ impl Execute for Statement {
                 constraints,
                 ..
             } => {
-                let ObjectName(name_idents) = name;
-                let table_name_str = unsafe_copy_str(&name_idents.get(0).expect("A valid table name must be provided").value);
+                let table_name_str = table_name_of(name)?;
 
                 let mut primary_keys: Vec<String> = Vec::new();
                 let mut indexes: Vec<String> = Vec::new();
-                let mut column_properies: HashMap<String, ColumnInfo> = HashMap::new();
+                let mut unique_constraints: Vec<UniqueConstraint> = Vec::new();
+                let mut column_properies: Vec<(String, ColumnInfo)> = Vec::new();
                 for column in columns {
                     let col_name_str = unsafe_copy_str(&column.name.value);
                     let data_type = column.data_type.clone();
@@ -40,11 +65,16 @@ impl Execute for Statement {
                             ColumnOption::Default(expr) => {column_property_list.push(ColumnProperties::Default(expr));},
                             ColumnOption::Unique {
                                 is_primary,
-                                characteristics
+                                ..
                             } => {
                                 if is_primary {
                                     column_property_list.push(ColumnProperties::PriamryKey);
                                     primary_keys.push(col_name_str.clone());
+                                } else {
+                                    unique_constraints.push(UniqueConstraint {
+                                        name: format!("UNIQUE {}", col_name_str),
+                                        columns: vec![col_name_str.clone()],
+                                    });
                                 }
                                 column_property_list.push(ColumnProperties::Unique);
                                 indexes.push(col_name_str.clone());
@@ -54,10 +84,9 @@ impl Execute for Statement {
                                 referred_columns,
                                 on_delete,
                                 on_update,
-                                characteristics
+                                ..
                             } => {
-                                let ObjectName(foreign_table_ident) = foreign_table;
-                                let f_table_name_str = foreign_table_ident.get(0).expect("A valid table name must be provided").value.clone();
+                                let f_table_name_str = table_name_of(&foreign_table)?;
                                 let ref_col_str_l = referred_columns.into_iter().map(|e| e.value).collect();
                                 column_property_list.push(ColumnProperties::ForeignKey {
                                     foreign_table: f_table_name_str,
@@ -68,7 +97,7 @@ impl Execute for Statement {
                             },
                             ColumnOption::OnUpdate(expr) => {column_property_list.push(ColumnProperties::OnUpdate(expr));},
                             ColumnOption::DialectSpecific(tokens) => {
-                                if let sqlparser::tokenizer::Token::Word(key_word) = tokens.get(0).unwrap(){
+                                if let Some(sqlparser::tokenizer::Token::Word(_key_word)) = tokens.first(){
                                     column_property_list.push(ColumnProperties::AutoIncrement);
                                 }
                             },
@@ -79,74 +108,179 @@ impl Execute for Statement {
                         type_name: data_type,
                         properties: column_property_list,
                     };
-                    column_properies.insert(col_name_str.clone(), col_info);
+                    column_properies.push((col_name_str.clone(), col_info));
+                }
+                for constraint in constraints {
+                    if let TableConstraint::Unique { name, columns, is_primary, .. } = constraint {
+                        let col_names: Vec<String> = columns.iter().map(|col| unsafe_copy_str(&col.value)).collect();
+                        if let Some(c_name) = col_names.iter().find(|c_name| !column_properies.iter().any(|(col_name, _)| col_name == *c_name)) {
+                            return Err(DbError::UnknownColumn(c_name.clone()));
+                        }
+                        if *is_primary {
+                            for c_name in col_names {
+                                if let Some((_, col_info)) = column_properies.iter_mut().find(|(col_name, _)| *col_name == c_name) {
+                                    col_info.properties.push(ColumnProperties::PriamryKey);
+                                }
+                                if !primary_keys.contains(&c_name) {
+                                    primary_keys.push(c_name);
+                                }
+                            }
+                        } else {
+                            unique_constraints.push(UniqueConstraint {
+                                name: name.as_ref().map(|ident| unsafe_copy_str(&ident.value)).unwrap_or_else(|| format!("UNIQUE ({})", col_names.join(", "))),
+                                columns: col_names,
+                            });
+                        }
+                    }
                 }
-                db.create_table(&table_name_str, primary_keys, indexes, column_properies);
+                db.create_table(&table_name_str, primary_keys, indexes, unique_constraints, column_properies);
 
                 Ok(SuccessStatus::TableCreated(table_name_str))
             },
             Statement::Insert {
                 table_name,
                 columns,
-                into,
                 source,
-                overwrite,
                 ..
             } => {
-                let ObjectName(name_idents) = table_name;
-                let table_name_str = unsafe_copy_str(&name_idents.get(0).expect("A valid table name must be provided").value);
-                let values = if let SetExpr::Values(value_list) = *source.clone().expect("No values to insert provided").body.clone() {
-                    value_list.rows
-                } else {
-                    Vec::new()
-                };
+                let table_name_str = table_name_of(table_name)?;
+                let source = source.as_ref().ok_or_else(|| DbError::Parse("No values to insert provided".to_string()))?;
                 let col_names: Vec<String> = columns.iter().map(|col| unsafe_copy_str(&col.value)).collect();
-                if let Some(ref table) = db.get_table_ref(&table_name_str){
-                    table.lock().unwrap().insert_values(&col_names, values);
-                    Ok(SuccessStatus::DataInserted)
-                }else {
-                    Err(format!("No table named {} exists", table_name_str))
+                let Some(table) = db.get_table_ref(&table_name_str) else {
+                    return Err(DbError::UnknownTable(table_name_str));
+                };
+                if let SetExpr::Values(value_list) = source.body.as_ref() {
+                    table.lock().unwrap().insert_values(&col_names, value_list.rows.clone())?;
+                } else {
+                    // the query runs before the table is locked, as it may read the table itself
+                    let result = execute_query(db, source)?;
+                    if result.columns.len() != col_names.len() {
+                        return Err(DbError::Execution("wrong number of columns".to_string()));
+                    }
+                    table.lock().unwrap().insert_query_rows(&col_names, result.rows)?;
                 }
+                Ok(SuccessStatus::DataInserted)
 
             },
-            Statement::Query (query) => {
-                match &*query.body {
-                    SetExpr::Select(select_q) => {
-                        let mut ret_value: Vec<u8> = Vec::new();
-                        for data_from in &*select_q.from {
-                            match &data_from.relation {
-                                TableFactor::Table {
-                                    name,
-                                    ..
-                                } => {
-                                    let ObjectName(name_idents) = name;
-                                    let table_name_str = name_idents.get(0).expect("A valid table name must be provided").value.clone();
-
-                                    if let Some(ref table) = db.get_table_ref(&table_name_str){
-                                        for projection in &select_q.projection {
-                                            match projection {
-                                                SelectItem::Wildcard(_options) => {
-                                                    //table.lock().unwrap().find_all(SelectFields::WildCard);
-                                                    ret_value.append(&mut bitcode::encode(&table.lock().unwrap().find_all(SelectFields::WildCard)));
-                                                    //ret_value.append(&mut serde_json::to_vec(&table.lock().unwrap().find_all(SelectFields::WildCard)).unwrap());
-                                                    
-                                                },
-                                                _=> {return Err("not yet implemented".to_string());}
-                                            }
-                                        }
-                                    }else {
-                                        return Err(format!("No table named {} exists", table_name_str));
-                                    }
-                                },
-                                _=> {return Err("not yet implemented".to_string());}
-                            }
+            Statement::Update {
+                table,
+                assignments,
+                selection,
+                ..
+            } => {
+                let (table_name_str, relation) = match &table.relation {
+                    TableFactor::Table { name, alias, .. } => {
+                        let table_name_str = table_name_of(name)?;
+                        let relation = alias.as_ref().map_or_else(|| table_name_str.clone(), |alias| alias.name.value.clone());
+                        (table_name_str, relation)
+                    },
+                    _ => { return Err(DbError::Unsupported(format!("UPDATE of {}", table.relation))); }
+                };
+                let mut assignment_list: Vec<(String, Expr)> = Vec::with_capacity(assignments.len());
+                for assignment in assignments {
+                    if let [.., qualifier, column] = assignment.id.as_slice() {
+                        if qualifier.value != relation {
+                            return Err(DbError::UnknownColumn(format!("{}.{}", qualifier.value, column.value)));
                         }
-                        Ok(SuccessStatus::DataFetched(ret_value))
+                    }
+                    check_qualifiers(&assignment.value, &relation)?;
+                    let c_name = assignment.id.last().map(|ident| unsafe_copy_str(&ident.value)).unwrap_or_default();
+                    assignment_list.push((c_name, assignment.value.clone()));
+                }
+                if let Some(selection) = selection {
+                    check_qualifiers(selection, &relation)?;
+                }
+                if let Some(table) = db.get_table_ref(&table_name_str){
+                    let exprs = assignment_list.iter().map(|(_, expr)| expr).chain(selection.as_ref());
+                    let updated_count = with_statement_scope(db, &table_name_str, &relation, exprs, |context| {
+                        table.lock().unwrap().update_values(&assignment_list, selection.as_ref(), context)
+                    })?;
+                    Ok(SuccessStatus::DataUpdated(updated_count))
+                }else {
+                    Err(DbError::UnknownTable(table_name_str))
+                }
+            },
+            Statement::Delete {
+                from,
+                selection,
+                ..
+            } => {
+                let (FromTable::WithFromKeyword(from_tables) | FromTable::WithoutKeyword(from_tables)) = from;
+                let (table_name_str, relation) = match from_tables.first().map(|from_table| &from_table.relation) {
+                    Some(TableFactor::Table { name, alias, .. }) => {
+                        let table_name_str = table_name_of(name)?;
+                        let relation = alias.as_ref().map_or_else(|| table_name_str.clone(), |alias| alias.name.value.clone());
+                        (table_name_str, relation)
                     },
-                    _=>{Err("not yet implemented".to_string())}
+                    _ => { return Err(DbError::Unsupported("DELETE without a single target table".to_string())); }
+                };
+                if let Some(selection) = selection {
+                    check_qualifiers(selection, &relation)?;
+                }
+                let deleted_count = with_statement_scope(db, &table_name_str, &relation, selection.as_ref(), |context| {
+                    db.delete_rows(&table_name_str, selection.as_ref(), context)
+                })?;
+                Ok(SuccessStatus::DataDeleted(deleted_count))
+            },
+            Statement::Query (query) => {
+                let result = execute_query(db, query)?;
+                let ret_value = bitcode::serialize(&result).map_err(|e| DbError::Execution(e.to_string()))?;
+                Ok(SuccessStatus::DataFetched(ret_value))
+            },
+            Statement::CreateIndex {
+                name,
+                table_name,
+                using,
+                columns,
+                unique,
+                if_not_exists,
+                include,
+                predicate,
+                ..
+            } => {
+                let table_name_str = table_name_of(table_name)?;
+                if !include.is_empty() || predicate.is_some() {
+                    return Err(DbError::Unsupported("INCLUDE columns and partial indexes".to_string()));
+                }
+                let kind = match using.as_ref().map(|ident| ident.value.to_uppercase()).as_deref() {
+                    None | Some("BTREE") => IndexKind::BTree,
+                    Some("HASH") => IndexKind::Hash,
+                    Some(method) => { return Err(DbError::Unsupported(format!("index method {}", method))); }
+                };
+                let mut col_names: Vec<String> = Vec::with_capacity(columns.len());
+                for column in columns {
+                    match &column.expr {
+                        Expr::Identifier(ident) => col_names.push(unsafe_copy_str(&ident.value)),
+                        expr => { return Err(DbError::Unsupported(format!("index on expression {}", expr))); }
+                    }
+                }
+                let index_name_str = match name {
+                    Some(name) => table_name_of(name)?,
+                    None => format!("{}_{}_idx", table_name_str, col_names.join("_")),
+                };
+                if *if_not_exists && db.index_exists(&index_name_str) {
+                    return Ok(SuccessStatus::IndexCreated(index_name_str));
+                }
+                db.create_index(&table_name_str, &index_name_str, &col_names, kind, *unique)?;
+                Ok(SuccessStatus::IndexCreated(index_name_str))
+            },
+            Statement::Drop {
+                object_type: ObjectType::Index,
+                if_exists,
+                names,
+                ..
+            } => {
+                let mut dropped = Vec::with_capacity(names.len());
+                for name in names {
+                    let index_name_str = table_name_of(name)?;
+                    if !db.drop_index(&index_name_str)? && !*if_exists {
+                        return Err(DbError::Execution(format!("No index named {} exists", index_name_str)));
+                    }
+                    dropped.push(index_name_str);
                 }
+                Ok(SuccessStatus::IndexDropped(dropped.join(", ")))
             },
-            _ => { Err("not yet implemented".to_string()) }
+            _ => { Err(DbError::Unsupported("statement".to_string())) }
         }
     }
 }
\ No newline at end of file
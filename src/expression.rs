@@ -0,0 +1,506 @@
+use std::cmp::Ordering;
+use std::rc::Rc;
+use sqlparser::ast::{BinaryOperator, CastFormat, DataType, DateTimeField, Expr, Function, FunctionArg, FunctionArgExpr, Interval, Query, TrimWhereField, UnaryOperator};
+use crate::aggregate::is_aggregate;
+use crate::error::DbError;
+use crate::executor::ResultSet;
+use crate::functions::call_function;
+use crate::validators::cast_value;
+use crate::value::{Decimal, Value};
+use crate::window::is_window;
+
+/// Resolves column references while evaluating an expression against one row.
+pub trait ColumnLookup {
+    fn lookup(&self, qualifier: Option<&str>, name: &str) -> Result<Value, DbError>;
+
+    /// Value the query already computed for `expr`, such as an aggregate or a GROUP BY key of the current group.
+    fn precomputed(&self, _expr: &Expr) -> Option<Value> {
+        None
+    }
+
+    /// Result of the subquery `query` run for `row`, the scope its correlated column references are read from.
+    fn run_subquery(&self, _query: &Query, _row: &dyn ColumnLookup) -> Result<Rc<ResultSet>, DbError> {
+        Err(DbError::Unsupported("subqueries in this statement".to_string()))
+    }
+}
+
+impl<R: ColumnLookup + ?Sized> ColumnLookup for &R {
+    fn lookup(&self, qualifier: Option<&str>, name: &str) -> Result<Value, DbError> {
+        (**self).lookup(qualifier, name)
+    }
+
+    fn precomputed(&self, expr: &Expr) -> Option<Value> {
+        (**self).precomputed(expr)
+    }
+
+    fn run_subquery(&self, query: &Query, row: &dyn ColumnLookup) -> Result<Rc<ResultSet>, DbError> {
+        (**self).run_subquery(query, row)
+    }
+}
+
+/// Scope without any column, for expressions evaluated outside of a row such as DEFAULT values.
+pub struct EmptyRow;
+
+impl ColumnLookup for EmptyRow {
+    fn lookup(&self, _qualifier: Option<&str>, name: &str) -> Result<Value, DbError> {
+        Err(DbError::UnknownColumn(name.to_string()))
+    }
+}
+
+/// Evaluates `expr` against `row`, with SQL three-valued logic (`Value::Null` is unknown).
+pub fn eval_expr(expr: &Expr, row: &dyn ColumnLookup) -> Result<Value, DbError> {
+    if let Some(value) = row.precomputed(expr) {
+        return Ok(value);
+    }
+    match expr {
+        Expr::Identifier(ident) => row.lookup(None, &ident.value),
+        Expr::CompoundIdentifier(idents) => {
+            match idents.as_slice() {
+                [column] => row.lookup(None, &column.value),
+                [.., qualifier, column] => row.lookup(Some(&qualifier.value), &column.value),
+                [] => Err(DbError::Parse("empty column reference".to_string())),
+            }
+        },
+        Expr::Value(value) => Ok(Value::from_sql_literal(value)),
+        Expr::Nested(inner) => eval_expr(inner, row),
+        Expr::UnaryOp { op, expr } => {
+            let value = eval_expr(expr, row)?;
+            match op {
+                UnaryOperator::Not => Ok(match truth_value(&value)? {
+                    Some(b) => Value::Bool(!b),
+                    None => Value::Null,
+                }),
+                UnaryOperator::Plus => Ok(value),
+                UnaryOperator::Minus => negate(value),
+                _ => Err(DbError::Unsupported(format!("unary operator {}", op))),
+            }
+        },
+        Expr::BinaryOp { left, op, right } => {
+            match op {
+                BinaryOperator::And => {
+                    let l = truth_value(&eval_expr(left, row)?)?;
+                    if l == Some(false) {
+                        return Ok(Value::Bool(false));
+                    }
+                    let r = truth_value(&eval_expr(right, row)?)?;
+                    Ok(match (l, r) {
+                        (_, Some(false)) => Value::Bool(false),
+                        (Some(true), Some(true)) => Value::Bool(true),
+                        _ => Value::Null,
+                    })
+                },
+                BinaryOperator::Or => {
+                    let l = truth_value(&eval_expr(left, row)?)?;
+                    if l == Some(true) {
+                        return Ok(Value::Bool(true));
+                    }
+                    let r = truth_value(&eval_expr(right, row)?)?;
+                    Ok(match (l, r) {
+                        (_, Some(true)) => Value::Bool(true),
+                        (Some(false), Some(false)) => Value::Bool(false),
+                        _ => Value::Null,
+                    })
+                },
+                _ => {
+                    let l = eval_expr(left, row)?;
+                    let r = eval_expr(right, row)?;
+                    eval_binary_op(&l, op, &r)
+                }
+            }
+        },
+        Expr::IsNull(inner) => Ok(Value::Bool(eval_expr(inner, row)?.is_null())),
+        Expr::IsNotNull(inner) => Ok(Value::Bool(!eval_expr(inner, row)?.is_null())),
+        Expr::IsTrue(inner) => Ok(Value::Bool(truth_value(&eval_expr(inner, row)?)? == Some(true))),
+        Expr::IsNotTrue(inner) => Ok(Value::Bool(truth_value(&eval_expr(inner, row)?)? != Some(true))),
+        Expr::IsFalse(inner) => Ok(Value::Bool(truth_value(&eval_expr(inner, row)?)? == Some(false))),
+        Expr::IsNotFalse(inner) => Ok(Value::Bool(truth_value(&eval_expr(inner, row)?)? != Some(false))),
+        Expr::IsUnknown(inner) => Ok(Value::Bool(truth_value(&eval_expr(inner, row)?)?.is_none())),
+        Expr::IsNotUnknown(inner) => Ok(Value::Bool(truth_value(&eval_expr(inner, row)?)?.is_some())),
+        Expr::IsDistinctFrom(left, right) => {
+            Ok(Value::Bool(eval_expr(left, row)? != eval_expr(right, row)?))
+        },
+        Expr::IsNotDistinctFrom(left, right) => {
+            Ok(Value::Bool(eval_expr(left, row)? == eval_expr(right, row)?))
+        },
+        Expr::InList { expr, list, negated } => {
+            let value = eval_expr(expr, row)?;
+            let list_values = list.iter().map(|item| eval_expr(item, row)).collect::<Result<Vec<Value>, DbError>>()?;
+            Ok(negate_if(in_values(&value, &list_values), *negated))
+        },
+        Expr::Between { expr, negated, low, high } => {
+            let value = eval_expr(expr, row)?;
+            let low_value = eval_expr(low, row)?;
+            let high_value = eval_expr(high, row)?;
+            let above_low = eval_binary_op(&value, &BinaryOperator::GtEq, &low_value)?;
+            let below_high = eval_binary_op(&value, &BinaryOperator::LtEq, &high_value)?;
+            let ret = match (truth_value(&above_low)?, truth_value(&below_high)?) {
+                (Some(false), _) | (_, Some(false)) => Value::Bool(false),
+                (Some(true), Some(true)) => Value::Bool(true),
+                _ => Value::Null,
+            };
+            Ok(negate_if(ret, *negated))
+        },
+        Expr::Function(function) if is_window(function) => Err(DbError::Execution(format!("window function {} is not allowed here", function.name))),
+        Expr::Function(function) if is_aggregate(function) => Err(DbError::Execution(format!("aggregate function {} is not allowed here", function.name))),
+        Expr::Function(function) => call_function(&function.name.to_string(), &eval_function_args(function, row)?),
+        Expr::Subquery(query) => {
+            let result = row.run_subquery(query, row)?;
+            match single_column(&result)?.as_slice() {
+                [] => Ok(Value::Null),
+                [value] => Ok(value.clone()),
+                _ => Err(DbError::Execution("more than one row returned by a subquery used as an expression".to_string())),
+            }
+        },
+        Expr::InSubquery { expr, subquery, negated } => {
+            let value = eval_expr(expr, row)?;
+            let result = row.run_subquery(subquery, row)?;
+            Ok(negate_if(in_values(&value, &single_column(&result)?), *negated))
+        },
+        Expr::Exists { subquery, negated } => {
+            let result = row.run_subquery(subquery, row)?;
+            Ok(Value::Bool(result.rows.is_empty() == *negated))
+        },
+        Expr::Like { negated, expr, pattern, escape_char } => {
+            eval_like(eval_expr(expr, row)?, eval_expr(pattern, row)?, *escape_char, false, *negated)
+        },
+        Expr::ILike { negated, expr, pattern, escape_char } => {
+            eval_like(eval_expr(expr, row)?, eval_expr(pattern, row)?, *escape_char, true, *negated)
+        },
+        Expr::Cast { expr, data_type, format } => eval_cast(eval_expr(expr, row)?, data_type, format.as_ref()),
+        // TRY_CAST and SAFE_CAST give NULL for a value the type cannot hold
+        Expr::TryCast { expr, data_type, format } | Expr::SafeCast { expr, data_type, format } => {
+            match eval_cast(eval_expr(expr, row)?, data_type, format.as_ref()) {
+                Err(DbError::Execution(_)) => Ok(Value::Null),
+                ret => ret,
+            }
+        },
+        Expr::TypedString { data_type, value } => eval_cast(Value::Text(value.clone()), data_type, None),
+        Expr::Case { operand, conditions, results, else_result } => {
+            let operand = operand.as_ref().map(|operand| eval_expr(operand, row)).transpose()?;
+            for (condition, result) in conditions.iter().zip(results) {
+                let matched = match &operand {
+                    // a simple CASE compares with =, so a NULL operand matches no branch
+                    Some(operand) => truth_value(&eval_binary_op(operand, &BinaryOperator::Eq, &eval_expr(condition, row)?)?)? == Some(true),
+                    None => eval_predicate(condition, row)?,
+                };
+                if matched {
+                    return eval_expr(result, row);
+                }
+            }
+            match else_result {
+                Some(else_result) => eval_expr(else_result, row),
+                None => Ok(Value::Null),
+            }
+        },
+        // functions with a syntax of their own call their plain counterpart
+        Expr::Substring { expr, substring_from, substring_for, .. } => {
+            let mut args = vec![eval_expr(expr, row)?, match substring_from {
+                Some(from) => eval_expr(from, row)?,
+                None => Value::Int64(1),
+            }];
+            if let Some(substring_for) = substring_for {
+                args.push(eval_expr(substring_for, row)?);
+            }
+            call_function("SUBSTRING", &args)
+        },
+        Expr::Trim { expr, trim_where, trim_what, trim_characters } => {
+            let function_name = match trim_where {
+                Some(TrimWhereField::Leading) => "LTRIM",
+                Some(TrimWhereField::Trailing) => "RTRIM",
+                _ => "TRIM",
+            };
+            let mut args = vec![eval_expr(expr, row)?];
+            for characters in trim_what.iter().map(|what| what.as_ref()).chain(trim_characters.iter().flatten()) {
+                args.push(eval_expr(characters, row)?);
+            }
+            call_function(function_name, &args)
+        },
+        Expr::Ceil { expr: inner, field } | Expr::Floor { expr: inner, field } => {
+            if *field != DateTimeField::NoDateTime {
+                return Err(DbError::Unsupported(format!("expression {}", expr)));
+            }
+            call_function(if matches!(expr, Expr::Ceil { .. }) { "CEIL" } else { "FLOOR" }, &[eval_expr(inner, row)?])
+        },
+        Expr::Position { expr, r#in } => call_function("POSITION", &[eval_expr(expr, row)?, eval_expr(r#in, row)?]),
+        Expr::Extract { field, expr } => call_function("DATE_PART", &[Value::Text(field.to_string()), eval_expr(expr, row)?]),
+        _ => Err(DbError::Unsupported(format!("expression {}", expr))),
+    }
+}
+
+/// `value` converted to `data_type`, failing with an Execution error when the type cannot hold it.
+fn eval_cast(value: Value, data_type: &DataType, format: Option<&CastFormat>) -> Result<Value, DbError> {
+    if let Some(format) = format {
+        return Err(DbError::Unsupported(format!("CAST ... FORMAT {}", format)));
+    }
+    cast_value(&value, data_type).map_err(|e| DbError::Execution(format!("cannot cast {} to {}: {}", value, data_type, e)))
+}
+
+/// Values of a subquery result that must have a single column.
+fn single_column(result: &ResultSet) -> Result<Vec<Value>, DbError> {
+    if result.columns.len() != 1 {
+        return Err(DbError::Execution(format!("subquery must return one column, got {}", result.columns.len())));
+    }
+    Ok(result.rows.iter().map(|row| row[0].clone()).collect())
+}
+
+/// `true` only when `expr` evaluates to TRUE, so NULL predicates filter rows out.
+pub fn eval_predicate(expr: &Expr, row: &dyn ColumnLookup) -> Result<bool, DbError> {
+    Ok(truth_value(&eval_expr(expr, row)?)? == Some(true))
+}
+
+/// Interprets a value as a SQL boolean, `None` standing for unknown.
+pub fn truth_value(value: &Value) -> Result<Option<bool>, DbError> {
+    match value {
+        Value::Null => Ok(None),
+        Value::Bool(b) => Ok(Some(*b)),
+        Value::Int64(_) | Value::UInt64(_) | Value::Float64(_) | Value::Decimal(_) => {
+            Ok(Some(value.as_f64() != Some(0.0)))
+        },
+        _ => Err(DbError::Execution(format!("{} is not a boolean value", value))),
+    }
+}
+
+fn negate_if(value: Value, negated: bool) -> Value {
+    match value {
+        Value::Bool(b) if negated => Value::Bool(!b),
+        _ => value,
+    }
+}
+
+fn negate(value: Value) -> Result<Value, DbError> {
+    match value {
+        Value::Null => Ok(Value::Null),
+        Value::Int64(v) => v.checked_neg().map(Value::Int64).ok_or_else(|| DbError::Execution("integer overflow".to_string())),
+        Value::UInt64(v) => {
+            if v <= i64::MAX as u64 + 1 {
+                Ok(Value::Int64((v as i128).wrapping_neg() as i64))
+            } else {
+                Ok(Value::Float64(-(v as f64)))
+            }
+        },
+        Value::Float64(v) => Ok(Value::Float64(-v)),
+        Value::Decimal(v) => v.mantissa.checked_neg()
+            .map(|mantissa| Value::Decimal(Decimal { mantissa, scale: v.scale }))
+            .ok_or_else(|| DbError::Execution("numeric overflow".to_string())),
+        other => Err(DbError::Execution(format!("cannot negate {}", other))),
+    }
+}
+
+/// `value IN (list)` with SQL semantics: NULL when there is no match but a NULL was involved.
+pub fn in_values(value: &Value, list: &[Value]) -> Value {
+    if value.is_null() {
+        return Value::Null;
+    }
+    let mut saw_null = false;
+    for item in list {
+        match value.compare(item) {
+            Some(Ordering::Equal) => return Value::Bool(true),
+            None if item.is_null() => saw_null = true,
+            _ => {}
+        }
+    }
+    if saw_null { Value::Null } else { Value::Bool(false) }
+}
+
+pub fn eval_binary_op(left: &Value, op: &BinaryOperator, right: &Value) -> Result<Value, DbError> {
+    let comparison = |accept: fn(Ordering) -> bool| -> Result<Value, DbError> {
+        if left.is_null() || right.is_null() {
+            return Ok(Value::Null);
+        }
+        match left.compare(right) {
+            Some(ordering) => Ok(Value::Bool(accept(ordering))),
+            None => Err(DbError::Execution(format!("cannot compare {} with {}", left, right))),
+        }
+    };
+    match op {
+        BinaryOperator::Eq => comparison(|o| o == Ordering::Equal),
+        BinaryOperator::NotEq => comparison(|o| o != Ordering::Equal),
+        BinaryOperator::Lt => comparison(|o| o == Ordering::Less),
+        BinaryOperator::LtEq => comparison(|o| o != Ordering::Greater),
+        BinaryOperator::Gt => comparison(|o| o == Ordering::Greater),
+        BinaryOperator::GtEq => comparison(|o| o != Ordering::Less),
+        BinaryOperator::Plus
+        | BinaryOperator::Minus
+        | BinaryOperator::Multiply
+        | BinaryOperator::Divide
+        | BinaryOperator::Modulo => eval_arithmetic(left, op, right),
+        BinaryOperator::StringConcat => {
+            if left.is_null() || right.is_null() {
+                Ok(Value::Null)
+            } else {
+                Ok(Value::Text(format!("{}{}", left, right)))
+            }
+        },
+        _ => Err(DbError::Unsupported(format!("operator {}", op))),
+    }
+}
+
+fn eval_arithmetic(left: &Value, op: &BinaryOperator, right: &Value) -> Result<Value, DbError> {
+    if left.is_null() || right.is_null() {
+        return Ok(Value::Null);
+    }
+    if !left.is_numeric() || !right.is_numeric() {
+        return Err(DbError::Execution(format!("cannot apply {} to {} and {}", op, left, right)));
+    }
+    let zero_divisor = matches!(op, BinaryOperator::Divide | BinaryOperator::Modulo) && right.as_f64() == Some(0.0);
+    if zero_divisor {
+        return Err(DbError::Execution("division by zero".to_string()));
+    }
+    match (left, right) {
+        (Value::Float64(_), _) | (_, Value::Float64(_)) => {
+            Ok(Value::Float64(float_op(left.as_f64().unwrap_or_default(), op, right.as_f64().unwrap_or_default())))
+        },
+        (Value::Decimal(_), _) | (_, Value::Decimal(_)) => {
+            let (l, r) = (as_decimal(left), as_decimal(right));
+            let exact = match (l, r, op) {
+                (Some(l), Some(r), BinaryOperator::Plus) => l.checked_add(r),
+                (Some(l), Some(r), BinaryOperator::Minus) => l.checked_sub(r),
+                (Some(l), Some(r), BinaryOperator::Multiply) => l.checked_mul(r),
+                _ => None,
+            };
+            match exact {
+                Some(v) => Ok(Value::Decimal(v)),
+                None => Ok(Value::Float64(float_op(left.as_f64().unwrap_or_default(), op, right.as_f64().unwrap_or_default()))),
+            }
+        },
+        _ => {
+            let l = integer_of(left);
+            let r = integer_of(right);
+            let ret = match op {
+                BinaryOperator::Plus => l.checked_add(r),
+                BinaryOperator::Minus => l.checked_sub(r),
+                BinaryOperator::Multiply => l.checked_mul(r),
+                BinaryOperator::Divide => l.checked_div(r),
+                _ => l.checked_rem(r),
+            }.ok_or_else(|| DbError::Execution("integer overflow".to_string()))?;
+            if let Ok(v) = i64::try_from(ret) {
+                Ok(Value::Int64(v))
+            } else if let Ok(v) = u64::try_from(ret) {
+                Ok(Value::UInt64(v))
+            } else {
+                Err(DbError::Execution("integer overflow".to_string()))
+            }
+        }
+    }
+}
+
+fn float_op(l: f64, op: &BinaryOperator, r: f64) -> f64 {
+    match op {
+        BinaryOperator::Plus => l + r,
+        BinaryOperator::Minus => l - r,
+        BinaryOperator::Multiply => l * r,
+        BinaryOperator::Divide => l / r,
+        _ => l % r,
+    }
+}
+
+fn integer_of(value: &Value) -> i128 {
+    match value {
+        Value::Int64(v) => *v as i128,
+        Value::UInt64(v) => *v as i128,
+        _ => 0,
+    }
+}
+
+fn as_decimal(value: &Value) -> Option<Decimal> {
+    match value {
+        Value::Decimal(v) => Some(*v),
+        Value::Int64(v) => Some(Decimal { mantissa: *v, scale: 0 }),
+        Value::UInt64(v) => i64::try_from(*v).ok().map(|mantissa| Decimal { mantissa, scale: 0 }),
+        _ => None,
+    }
+}
+
+/// Evaluates the plain (non wildcard) arguments of a function call. An INTERVAL argument stands for two:
+/// its count and its unit, so that `DATE_ADD(d, INTERVAL 1 DAY)` calls `DATE_ADD(d, 1, 'DAY')`.
+pub fn eval_function_args(function: &Function, row: &dyn ColumnLookup) -> Result<Vec<Value>, DbError> {
+    let mut args = Vec::with_capacity(function.args.len());
+    for arg in &function.args {
+        match arg {
+            FunctionArg::Unnamed(FunctionArgExpr::Expr(Expr::Interval(interval))) => args.extend(eval_interval(interval, row)?),
+            FunctionArg::Unnamed(FunctionArgExpr::Expr(expr)) | FunctionArg::Named { arg: FunctionArgExpr::Expr(expr), .. } => args.push(eval_expr(expr, row)?),
+            _ => { return Err(DbError::Unsupported(format!("argument {} for {}", arg, function.name))); }
+        }
+    }
+    Ok(args)
+}
+
+/// Count and unit of `INTERVAL 2 DAY` or `INTERVAL '2 days'`.
+fn eval_interval(interval: &Interval, row: &dyn ColumnLookup) -> Result<[Value; 2], DbError> {
+    let value = eval_expr(&interval.value, row)?;
+    if let Some(unit) = interval.leading_field {
+        return Ok([value, Value::Text(unit.to_string())]);
+    }
+    let text = value.to_string();
+    match text.split_whitespace().collect::<Vec<_>>().as_slice() {
+        [count, unit] => Ok([Value::Text(count.to_string()), Value::Text(unit.to_string())]),
+        _ => Err(DbError::Execution(format!("interval {} must be a count followed by a unit", text))),
+    }
+}
+
+fn eval_like(value: Value, pattern: Value, escape_char: Option<char>, case_insensitive: bool, negated: bool) -> Result<Value, DbError> {
+    match (value, pattern) {
+        (Value::Null, _) | (_, Value::Null) => Ok(Value::Null),
+        (value, Value::Text(pattern)) => {
+            let text = value.to_string();
+            let matched = if case_insensitive {
+                like_match(&text.to_lowercase(), &pattern.to_lowercase(), escape_char)
+            } else {
+                like_match(&text, &pattern, escape_char)
+            };
+            Ok(Value::Bool(matched != negated))
+        },
+        (_, pattern) => Err(DbError::Execution(format!("LIKE pattern must be a string, got {}", pattern))),
+    }
+}
+
+/// Matches `%` (any sequence) and `_` (any single character) wildcards.
+pub fn like_match(text: &str, pattern: &str, escape_char: Option<char>) -> bool {
+    enum Token { Any, One, Char(char) }
+    let mut tokens = Vec::new();
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        if Some(c) == escape_char {
+            if let Some(escaped) = chars.next() {
+                tokens.push(Token::Char(escaped));
+            }
+        } else if c == '%' {
+            tokens.push(Token::Any);
+        } else if c == '_' {
+            tokens.push(Token::One);
+        } else {
+            tokens.push(Token::Char(c));
+        }
+    }
+    let text: Vec<char> = text.chars().collect();
+    // greedy matching with backtracking to the last `%`
+    let (mut t, mut p) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+    while t < text.len() {
+        match tokens.get(p) {
+            Some(Token::Any) => {
+                backtrack = Some((p, t));
+                p += 1;
+            },
+            Some(Token::One) => {
+                t += 1;
+                p += 1;
+            },
+            Some(Token::Char(c)) if *c == text[t] => {
+                t += 1;
+                p += 1;
+            },
+            _ => {
+                match backtrack {
+                    Some((any_p, any_t)) => {
+                        backtrack = Some((any_p, any_t + 1));
+                        p = any_p + 1;
+                        t = any_t + 1;
+                    },
+                    None => return false,
+                }
+            }
+        }
+    }
+    tokens[p..].iter().all(|token| matches!(token, Token::Any))
+}
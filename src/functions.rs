@@ -0,0 +1,390 @@
+use std::cmp::Ordering;
+use chrono::{Datelike, Months, NaiveDate, NaiveDateTime, NaiveTime, TimeDelta, Timelike};
+use sqlparser::ast::BinaryOperator;
+use crate::error::DbError;
+use crate::expression::eval_binary_op;
+use crate::value::{Decimal, Rounding, Value};
+
+/// A built-in scalar function, computing one value from the values of its arguments.
+pub struct ScalarFunction {
+    name: &'static str,
+    min_args: usize,
+    max_args: usize,//usize::MAX when any number of arguments is accepted
+    strict: bool,//NULL as soon as an argument is NULL, without calling `eval`
+    eval: fn(&[Value]) -> Result<Value, DbError>,
+}
+
+impl ScalarFunction {
+    const fn strict(name: &'static str, min_args: usize, max_args: usize, eval: fn(&[Value]) -> Result<Value, DbError>) -> Self {
+        Self { name, min_args, max_args, strict: true, eval }
+    }
+
+    const fn null_aware(name: &'static str, min_args: usize, max_args: usize, eval: fn(&[Value]) -> Result<Value, DbError>) -> Self {
+        Self { name, min_args, max_args, strict: false, eval }
+    }
+
+    /// Value of the function for `args`, after checking how many there are.
+    pub fn call(&self, args: &[Value]) -> Result<Value, DbError> {
+        if args.len() < self.min_args || args.len() > self.max_args {
+            let expected = match self.max_args {
+                usize::MAX => format!("at least {}", self.min_args),
+                max_args if max_args == self.min_args => max_args.to_string(),
+                max_args => format!("{} to {}", self.min_args, max_args),
+            };
+            return Err(DbError::Execution(format!("{} expects {} argument(s), got {}", self.name, expected, args.len())));
+        }
+        if self.strict && args.iter().any(Value::is_null) {
+            return Ok(Value::Null);
+        }
+        (self.eval)(args)
+    }
+}
+
+/// Every built-in scalar function, looked up by name regardless of case.
+const SCALAR_FUNCTIONS: &[ScalarFunction] = &[
+    // strings
+    ScalarFunction::strict("LOWER", 1, 1, |args| Ok(Value::Text(args[0].to_string().to_lowercase()))),
+    ScalarFunction::strict("UPPER", 1, 1, |args| Ok(Value::Text(args[0].to_string().to_uppercase()))),
+    ScalarFunction::strict("LENGTH", 1, 1, length),
+    ScalarFunction::strict("CHAR_LENGTH", 1, 1, length),
+    ScalarFunction::strict("SUBSTRING", 2, 3, substring),
+    ScalarFunction::strict("SUBSTR", 2, 3, substring),
+    ScalarFunction::strict("TRIM", 1, 2, |args| trim(args, true, true)),
+    ScalarFunction::strict("LTRIM", 1, 2, |args| trim(args, true, false)),
+    ScalarFunction::strict("RTRIM", 1, 2, |args| trim(args, false, true)),
+    ScalarFunction::null_aware("CONCAT", 0, usize::MAX, |args| Ok(Value::Text(args.iter().filter(|v| !v.is_null()).map(|v| v.to_string()).collect()))),
+    ScalarFunction::strict("REPLACE", 3, 3, replace),
+    ScalarFunction::strict("POSITION", 2, 2, position),
+    // numbers
+    ScalarFunction::strict("ABS", 1, 1, abs),
+    ScalarFunction::strict("ROUND", 1, 2, |args| round(args, "ROUND", Rounding::HalfAwayFromZero)),
+    ScalarFunction::strict("FLOOR", 1, 1, |args| round(args, "FLOOR", Rounding::Down)),
+    ScalarFunction::strict("CEIL", 1, 1, |args| round(args, "CEIL", Rounding::Up)),
+    ScalarFunction::strict("CEILING", 1, 1, |args| round(args, "CEILING", Rounding::Up)),
+    ScalarFunction::strict("MOD", 2, 2, |args| eval_binary_op(&args[0], &BinaryOperator::Modulo, &args[1])),
+    ScalarFunction::strict("POWER", 2, 2, power),
+    ScalarFunction::strict("POW", 2, 2, power),
+    // NULL handling
+    ScalarFunction::null_aware("COALESCE", 1, usize::MAX, |args| Ok(args.iter().find(|v| !v.is_null()).cloned().unwrap_or(Value::Null))),
+    ScalarFunction::null_aware("IFNULL", 2, 2, |args| Ok(if args[0].is_null() { args[1].clone() } else { args[0].clone() })),
+    ScalarFunction::null_aware("NULLIF", 2, 2, |args| Ok(if args[0].compare(&args[1]) == Some(Ordering::Equal) { Value::Null } else { args[0].clone() })),
+    // dates and times
+    ScalarFunction::null_aware("NOW", 0, 0, |_| Ok(Value::Timestamp(chrono::Local::now().naive_local()))),
+    ScalarFunction::null_aware("CURRENT_TIMESTAMP", 0, 0, |_| Ok(Value::Timestamp(chrono::Local::now().naive_local()))),
+    ScalarFunction::null_aware("CURRENT_DATE", 0, 0, |_| Ok(Value::Date(chrono::Local::now().date_naive()))),
+    ScalarFunction::null_aware("CURRENT_TIME", 0, 0, |_| Ok(Value::Time(chrono::Local::now().time()))),
+    ScalarFunction::strict("DATE_ADD", 3, 3, |args| date_add(args, "DATE_ADD", false)),
+    ScalarFunction::strict("DATE_SUB", 3, 3, |args| date_add(args, "DATE_SUB", true)),
+    ScalarFunction::strict("DATE_PART", 2, 2, date_part),
+    ScalarFunction::strict("DATE_TRUNC", 2, 2, date_trunc),
+    // conversions
+    ScalarFunction::strict("TO_CHAR", 1, 2, to_char),
+    ScalarFunction::strict("TO_NUMBER", 1, 1, to_number),
+    ScalarFunction::strict("TO_DATE", 1, 2, |args| parse_temporal(args, "TO_DATE", true)),
+    ScalarFunction::strict("TO_TIMESTAMP", 1, 2, |args| parse_temporal(args, "TO_TIMESTAMP", false)),
+];
+
+/// The built-in scalar function named `name`, if any.
+pub fn scalar_function(name: &str) -> Option<&'static ScalarFunction> {
+    SCALAR_FUNCTIONS.iter().find(|function| function.name.eq_ignore_ascii_case(name))
+}
+
+/// Calls the built-in scalar function named `name` with `args`.
+pub fn call_function(name: &str, args: &[Value]) -> Result<Value, DbError> {
+    match scalar_function(name) {
+        Some(function) => function.call(args),
+        None => Err(DbError::Unsupported(format!("function {}", name))),
+    }
+}
+
+fn integer_arg(value: &Value, function_name: &str) -> Result<i64, DbError> {
+    match value {
+        Value::Int64(v) => Ok(*v),
+        Value::UInt64(v) => i64::try_from(*v).map_err(|_| DbError::Execution(format!("{} argument {} is out of range", function_name, v))),
+        Value::Text(v) => v.trim().parse().map_err(|_| DbError::Execution(format!("{} expects an integer, got {}", function_name, value))),
+        _ => Err(DbError::Execution(format!("{} expects an integer, got {}", function_name, value))),
+    }
+}
+
+fn integer_value(value: i128) -> Result<Value, DbError> {
+    if let Ok(v) = i64::try_from(value) {
+        Ok(Value::Int64(v))
+    } else if let Ok(v) = u64::try_from(value) {
+        Ok(Value::UInt64(v))
+    } else {
+        Err(DbError::Execution("integer overflow".to_string()))
+    }
+}
+
+fn length(args: &[Value]) -> Result<Value, DbError> {
+    Ok(Value::Int64(args[0].to_string().chars().count() as i64))
+}
+
+/// SUBSTRING(text, start[, length]), `start` counting characters from 1. The characters a start before 1
+/// would skip count towards `length`.
+fn substring(args: &[Value]) -> Result<Value, DbError> {
+    let text = args[0].to_string();
+    let start = integer_arg(&args[1], "SUBSTRING")?;
+    let end = match args.get(2) {
+        Some(length) => match integer_arg(length, "SUBSTRING")? {
+            length if length < 0 => { return Err(DbError::Execution("negative substring length not allowed".to_string())); }
+            length => start.saturating_add(length),
+        },
+        None => i64::MAX,
+    };
+    let first = start.max(1);
+    let taken = end.saturating_sub(first).max(0);
+    Ok(Value::Text(text.chars().skip((first - 1) as usize).take(taken.try_into().unwrap_or(usize::MAX)).collect()))
+}
+
+/// TRIM(text[, characters]), stripping spaces unless told which characters to strip.
+fn trim(args: &[Value], leading: bool, trailing: bool) -> Result<Value, DbError> {
+    let text = args[0].to_string();
+    let characters: Vec<char> = match args.get(1) {
+        Some(characters) => characters.to_string().chars().collect(),
+        None => vec![' '],
+    };
+    let mut trimmed = text.as_str();
+    if leading {
+        trimmed = trimmed.trim_start_matches(characters.as_slice());
+    }
+    if trailing {
+        trimmed = trimmed.trim_end_matches(characters.as_slice());
+    }
+    Ok(Value::Text(trimmed.to_string()))
+}
+
+fn replace(args: &[Value]) -> Result<Value, DbError> {
+    let (text, from) = (args[0].to_string(), args[1].to_string());
+    if from.is_empty() {
+        return Ok(Value::Text(text));
+    }
+    Ok(Value::Text(text.replace(&from, &args[2].to_string())))
+}
+
+/// POSITION(substring, text): 1-based character position of the first occurrence, 0 when there is none.
+fn position(args: &[Value]) -> Result<Value, DbError> {
+    let (substring, text) = (args[0].to_string(), args[1].to_string());
+    Ok(Value::Int64(match text.find(&substring) {
+        Some(byte_position) => text[..byte_position].chars().count() as i64 + 1,
+        None => 0,
+    }))
+}
+
+fn abs(args: &[Value]) -> Result<Value, DbError> {
+    match &args[0] {
+        value if value.compare(&Value::Int64(0)) == Some(Ordering::Less) => eval_binary_op(&Value::Int64(0), &BinaryOperator::Minus, value),
+        value if value.is_numeric() => Ok(value.clone()),
+        value => Err(DbError::Execution(format!("ABS expects a number, got {}", value))),
+    }
+}
+
+/// ROUND(number[, digits]), FLOOR(number) and CEIL(number), keeping the kind of number they are given.
+/// Negative digits round to tens, hundreds and so on.
+fn round(args: &[Value], function_name: &str, rounding: Rounding) -> Result<Value, DbError> {
+    let digits = args.get(1).map(|digits| integer_arg(digits, function_name)).transpose()?.unwrap_or(0).clamp(-38, 38);
+    match &args[0] {
+        // floats are rounded as the decimal they print as, like CAST rounds them; those no decimal can hold are left as they are
+        Value::Float64(v) => match Decimal::from_f64(*v).and_then(|v| v.round_to(digits, rounding)) {
+            Some(rounded) => Ok(Value::Float64(rounded.to_f64())),
+            None => Ok(args[0].clone()),
+        },
+        Value::Int64(_) | Value::UInt64(_) if digits >= 0 => Ok(args[0].clone()),
+        Value::Int64(_) | Value::UInt64(_) => {
+            let v = match args[0] {
+                Value::Int64(v) => v as i128,
+                Value::UInt64(v) => v as i128,
+                _ => 0,
+            };
+            let factor = 10i128.pow(-digits as u32);
+            integer_value(rounding.divide(v, factor) * factor)
+        },
+        Value::Decimal(v) => {
+            let rounded = v.round_to(digits, rounding).ok_or_else(|| DbError::Execution("integer overflow".to_string()))?;
+            if digits >= 0 {
+                Ok(Value::Decimal(rounded))
+            } else {
+                Ok(Value::Int64(rounded.mantissa))
+            }
+        },
+        value => Err(DbError::Execution(format!("{} expects a number, got {}", function_name, value))),
+    }
+}
+
+/// POWER(base, exponent), exact for integers raised to a non-negative integer power.
+fn power(args: &[Value]) -> Result<Value, DbError> {
+    let (base, exponent) = (&args[0], &args[1]);
+    if !base.is_numeric() || !exponent.is_numeric() {
+        return Err(DbError::Execution(format!("POWER expects numbers, got {} and {}", base, exponent)));
+    }
+    if let (Value::Int64(_) | Value::UInt64(_), Value::Int64(_) | Value::UInt64(_)) = (base, exponent) {
+        let exact = integer_arg(base, "POWER").ok().zip(integer_arg(exponent, "POWER").ok().and_then(|e| u32::try_from(e).ok()))
+            .and_then(|(base, exponent)| base.checked_pow(exponent));
+        if let Some(v) = exact {
+            return Ok(Value::Int64(v));
+        }
+    }
+    let ret = base.as_f64().unwrap_or_default().powf(exponent.as_f64().unwrap_or_default());
+    if ret.is_nan() {
+        return Err(DbError::Execution(format!("POWER of {} to {} is not a real number", base, exponent)));
+    }
+    Ok(Value::Float64(ret))
+}
+
+/// Timestamp held by a date, a timestamp or a text in one of their formats, along with whether it was a date.
+fn timestamp_of(value: &Value, function_name: &str) -> Result<(NaiveDateTime, bool), DbError> {
+    let invalid = || DbError::Execution(format!("{} expects a date or a timestamp, got {}", function_name, value));
+    match value {
+        Value::Date(v) => Ok((v.and_time(NaiveTime::MIN), true)),
+        Value::Timestamp(v) => Ok((*v, false)),
+        Value::Text(v) => match NaiveDateTime::parse_from_str(v, "%Y-%m-%d %H:%M:%S%.f") {
+            Ok(v) => Ok((v, false)),
+            Err(_) => NaiveDate::parse_from_str(v, "%Y-%m-%d").map(|v| (v.and_time(NaiveTime::MIN), true)).map_err(|_| invalid()),
+        },
+        _ => Err(invalid()),
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, PartialOrd)]
+enum DateUnit {
+    Year,
+    Quarter,
+    Month,
+    Week,
+    Day,
+    Hour,
+    Minute,
+    Second,
+}
+
+impl DateUnit {
+    fn parse(value: &Value) -> Result<Self, DbError> {
+        let name = value.to_string().to_uppercase();
+        Ok(match name.strip_suffix('S').unwrap_or(&name) {
+            "YEAR" => DateUnit::Year,
+            "QUARTER" => DateUnit::Quarter,
+            "MONTH" => DateUnit::Month,
+            "WEEK" => DateUnit::Week,
+            "DAY" => DateUnit::Day,
+            "HOUR" => DateUnit::Hour,
+            "MINUTE" => DateUnit::Minute,
+            "SECOND" => DateUnit::Second,
+            _ => { return Err(DbError::Unsupported(format!("date unit {}", value))); }
+        })
+    }
+}
+
+/// DATE_ADD(date, count, unit) and DATE_SUB. Dates stay dates unless a unit smaller than a day is added.
+fn date_add(args: &[Value], function_name: &str, subtract: bool) -> Result<Value, DbError> {
+    let (timestamp, is_date) = timestamp_of(&args[0], function_name)?;
+    let count = integer_arg(&args[1], function_name)?;
+    let count = if subtract { count.checked_neg() } else { Some(count) };
+    let unit = DateUnit::parse(&args[2])?;
+    let months = |count: i64, per_unit: i64| -> Option<NaiveDateTime> {
+        let months = u32::try_from(count.checked_mul(per_unit)?.unsigned_abs()).ok()?;
+        if count < 0 { timestamp.checked_sub_months(Months::new(months)) } else { timestamp.checked_add_months(Months::new(months)) }
+    };
+    let ret = count.and_then(|count| match unit {
+        DateUnit::Year => months(count, 12),
+        DateUnit::Quarter => months(count, 3),
+        DateUnit::Month => months(count, 1),
+        DateUnit::Week => timestamp.checked_add_signed(TimeDelta::try_weeks(count)?),
+        DateUnit::Day => timestamp.checked_add_signed(TimeDelta::try_days(count)?),
+        DateUnit::Hour => timestamp.checked_add_signed(TimeDelta::try_hours(count)?),
+        DateUnit::Minute => timestamp.checked_add_signed(TimeDelta::try_minutes(count)?),
+        DateUnit::Second => timestamp.checked_add_signed(TimeDelta::try_seconds(count)?),
+    }).ok_or_else(|| DbError::Execution(format!("{} result is out of range", function_name)))?;
+    if is_date && unit <= DateUnit::Day {
+        Ok(Value::Date(ret.date()))
+    } else {
+        Ok(Value::Timestamp(ret))
+    }
+}
+
+/// DATE_PART(field, value), which EXTRACT(field FROM value) also calls.
+fn date_part(args: &[Value]) -> Result<Value, DbError> {
+    let field = args[0].to_string().to_uppercase();
+    let (date, time) = match &args[1] {
+        Value::Time(time) => (None, *time),
+        value => {
+            let (timestamp, _) = timestamp_of(value, "DATE_PART")?;
+            (Some(timestamp.date()), timestamp.time())
+        },
+    };
+    let date_field = |part: fn(NaiveDate) -> i64| date.map(part).ok_or_else(|| DbError::Execution(format!("a time has no {} part", field)));
+    let part = match field.as_str() {
+        "YEAR" => date_field(|date| date.year() as i64)?,
+        "QUARTER" => date_field(|date| (date.month0() / 3 + 1) as i64)?,
+        "MONTH" => date_field(|date| date.month() as i64)?,
+        "WEEK" => date_field(|date| date.iso_week().week() as i64)?,
+        "DAY" => date_field(|date| date.day() as i64)?,
+        "DOW" | "DAYOFWEEK" => date_field(|date| date.weekday().num_days_from_sunday() as i64)?,
+        "DOY" | "DAYOFYEAR" => date_field(|date| date.ordinal() as i64)?,
+        "HOUR" => time.hour() as i64,
+        "MINUTE" => time.minute() as i64,
+        "SECOND" => time.second() as i64,
+        "EPOCH" => date_field(|date| date.and_time(NaiveTime::MIN).and_utc().timestamp())? + time.num_seconds_from_midnight() as i64,
+        _ => { return Err(DbError::Unsupported(format!("date part {}", field))); }
+    };
+    Ok(Value::Int64(part))
+}
+
+/// DATE_TRUNC(unit, value): the start of the unit `value` falls in, weeks starting on Monday.
+fn date_trunc(args: &[Value]) -> Result<Value, DbError> {
+    let unit = DateUnit::parse(&args[0])?;
+    let (timestamp, is_date) = timestamp_of(&args[1], "DATE_TRUNC")?;
+    let date = timestamp.date();
+    let time = timestamp.time();
+    let truncated = match unit {
+        DateUnit::Year => date.with_ordinal(1).map(|date| date.and_time(NaiveTime::MIN)),
+        DateUnit::Quarter => date.with_day(1).and_then(|date| date.with_month(date.month0() / 3 * 3 + 1)).map(|date| date.and_time(NaiveTime::MIN)),
+        DateUnit::Month => date.with_day(1).map(|date| date.and_time(NaiveTime::MIN)),
+        DateUnit::Week => date.checked_sub_signed(TimeDelta::days(date.weekday().num_days_from_monday() as i64)).map(|date| date.and_time(NaiveTime::MIN)),
+        DateUnit::Day => Some(date.and_time(NaiveTime::MIN)),
+        DateUnit::Hour => NaiveTime::from_hms_opt(time.hour(), 0, 0).map(|time| date.and_time(time)),
+        DateUnit::Minute => NaiveTime::from_hms_opt(time.hour(), time.minute(), 0).map(|time| date.and_time(time)),
+        DateUnit::Second => NaiveTime::from_hms_opt(time.hour(), time.minute(), time.second()).map(|time| date.and_time(time)),
+    }.ok_or_else(|| DbError::Execution("DATE_TRUNC result is out of range".to_string()))?;
+    if is_date {
+        Ok(Value::Date(truncated.date()))
+    } else {
+        Ok(Value::Timestamp(truncated))
+    }
+}
+
+/// TO_CHAR(value[, format]), dates and times being formatted with chrono's strftime-like `format`.
+fn to_char(args: &[Value]) -> Result<Value, DbError> {
+    let Some(format) = args.get(1) else {
+        return Ok(Value::Text(args[0].to_string()));
+    };
+    let format = format.to_string();
+    let formatted = match &args[0] {
+        Value::Date(v) => v.format(&format),
+        Value::Time(v) => v.format(&format),
+        Value::Timestamp(v) => v.format(&format),
+        value => { return Err(DbError::Execution(format!("TO_CHAR formats dates and times, got {}", value))); }
+    };
+    // chrono reports a bad format only when writing it out
+    let mut text = String::new();
+    std::fmt::write(&mut text, format_args!("{}", formatted)).map_err(|_| DbError::Execution(format!("invalid TO_CHAR format {}", format)))?;
+    Ok(Value::Text(text))
+}
+
+fn to_number(args: &[Value]) -> Result<Value, DbError> {
+    match &args[0] {
+        value if value.is_numeric() => Ok(value.clone()),
+        value => Value::parse_number(value.to_string().trim()).ok_or_else(|| DbError::Execution(format!("TO_NUMBER cannot read {} as a number", value))),
+    }
+}
+
+/// TO_DATE(text[, format]) and TO_TIMESTAMP, reading `text` with chrono's strftime-like `format`, ISO 8601 by default.
+fn parse_temporal(args: &[Value], function_name: &str, is_date: bool) -> Result<Value, DbError> {
+    let text = args[0].to_string();
+    let parsed = match (args.get(1), is_date) {
+        (Some(format), true) => NaiveDate::parse_from_str(&text, &format.to_string()).map(Value::Date),
+        (None, true) => NaiveDate::parse_from_str(&text, "%Y-%m-%d").map(Value::Date),
+        (Some(format), false) => NaiveDateTime::parse_from_str(&text, &format.to_string()).map(Value::Timestamp),
+        (None, false) => NaiveDateTime::parse_from_str(&text, "%Y-%m-%d %H:%M:%S%.f").map(Value::Timestamp),
+    };
+    parsed.map_err(|error| DbError::Execution(format!("{} cannot read {}: {}", function_name, text, error)))
+}
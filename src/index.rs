@@ -0,0 +1,219 @@
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Bound;
+use sqlparser::ast::{BinaryOperator, Expr};
+use crate::data_descriptor::RowKey;
+use crate::expression::{EmptyRow, eval_expr};
+use crate::value::Value;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IndexKind {
+    Hash,//equality lookups only
+    BTree,//equality and range lookups on the leading column
+}
+
+#[derive(Debug)]
+enum IndexEntries {
+    Hash(HashMap<Vec<Value>, Vec<RowKey>>),
+    BTree(BTreeMap<Vec<Value>, Vec<RowKey>>),
+}
+
+/// Secondary index mapping the values of one or more columns to the keys of the rows holding them.
+#[derive(Debug)]
+pub struct SecondaryIndex {
+    name: String,
+    columns: Vec<String>,
+    positions: Vec<usize>,//positions of `columns` in the table rows
+    unique: bool,
+    entries: IndexEntries,
+}
+
+impl SecondaryIndex {
+    pub fn new(name: String, columns: Vec<String>, positions: Vec<usize>, kind: IndexKind, unique: bool) -> Self {
+        let entries = match kind {
+            IndexKind::Hash => IndexEntries::Hash(HashMap::new()),
+            IndexKind::BTree => IndexEntries::BTree(BTreeMap::new()),
+        };
+        Self { name, columns, positions, unique, entries }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn columns(&self) -> &[String] {
+        &self.columns
+    }
+
+    pub fn kind(&self) -> IndexKind {
+        match self.entries {
+            IndexEntries::Hash(_) => IndexKind::Hash,
+            IndexEntries::BTree(_) => IndexKind::BTree,
+        }
+    }
+
+    pub fn is_unique(&self) -> bool {
+        self.unique
+    }
+
+    /// Values of the indexed columns in `column_data`.
+    pub fn key_of(&self, column_data: &[Value]) -> Vec<Value> {
+        self.positions.iter().map(|position| column_data[*position].clone()).collect()
+    }
+
+    pub fn insert(&mut self, row_key: &RowKey, column_data: &[Value]) {
+        let key = self.key_of(column_data);
+        let row_keys = match &mut self.entries {
+            IndexEntries::Hash(entries) => entries.entry(key).or_default(),
+            IndexEntries::BTree(entries) => entries.entry(key).or_default(),
+        };
+        row_keys.push(row_key.clone());
+    }
+
+    pub fn remove(&mut self, row_key: &RowKey, column_data: &[Value]) {
+        let key = self.key_of(column_data);
+        let row_keys = match &mut self.entries {
+            IndexEntries::Hash(entries) => entries.get_mut(&key),
+            IndexEntries::BTree(entries) => entries.get_mut(&key),
+        };
+        let Some(row_keys) = row_keys else {
+            return;
+        };
+        row_keys.retain(|k| k != row_key);
+        if row_keys.is_empty() {
+            match &mut self.entries {
+                IndexEntries::Hash(entries) => entries.remove(&key),
+                IndexEntries::BTree(entries) => entries.remove(&key),
+            };
+        }
+    }
+
+    /// Keys of the rows whose indexed columns equal `key`.
+    pub fn get(&self, key: &[Value]) -> &[RowKey] {
+        let row_keys = match &self.entries {
+            IndexEntries::Hash(entries) => entries.get(key),
+            IndexEntries::BTree(entries) => entries.get(key),
+        };
+        row_keys.map(|row_keys| row_keys.as_slice()).unwrap_or(&[])
+    }
+
+    /// Keys of every row in the order of the indexed values, None for hash indexes.
+    pub fn ordered_keys(&self, descending: bool) -> Option<Box<dyn Iterator<Item = &RowKey> + '_>> {
+        let IndexEntries::BTree(entries) = &self.entries else {
+            return None;
+        };
+        if descending {
+            Some(Box::new(entries.values().rev().flatten()))
+        } else {
+            Some(Box::new(entries.values().flatten()))
+        }
+    }
+
+    /// Keys of the rows whose leading indexed column lies between `lower` and `upper`, None for hash indexes.
+    pub fn range(&self, lower: Bound<&Value>, upper: Bound<&Value>) -> Option<Vec<RowKey>> {
+        let IndexEntries::BTree(entries) = &self.entries else {
+            return None;
+        };
+        let start = match lower {
+            Bound::Included(v) | Bound::Excluded(v) => Bound::Included(vec![v.clone()]),
+            Bound::Unbounded => Bound::Unbounded,
+        };
+        let mut ret = Vec::new();
+        for (key, row_keys) in entries.range((start, Bound::Unbounded)) {
+            let leading = &key[0];
+            if let Bound::Excluded(v) = lower {
+                if leading == v {
+                    continue;
+                }
+            }
+            let below_upper = match upper {
+                Bound::Included(v) => leading <= v,
+                Bound::Excluded(v) => leading < v,
+                Bound::Unbounded => true,
+            };
+            if !below_upper {
+                break;
+            }
+            ret.extend(row_keys.iter().cloned());
+        }
+        Some(ret)
+    }
+}
+
+/// Restriction put on a single column by one conjunct of a WHERE clause.
+#[derive(Debug)]
+pub enum ColumnBound {
+    /// The column equals one of the values (`=` or `IN`).
+    OneOf(Vec<Value>),
+    Range(Bound<Value>, Bound<Value>),
+}
+
+/// Column restrictions implied by the AND-ed conjuncts of `predicate` that compare a column with constants.
+/// Every row satisfying `predicate` satisfies each returned restriction.
+pub fn column_bounds(predicate: &Expr) -> Vec<(String, ColumnBound)> {
+    let mut ret = Vec::new();
+    collect_column_bounds(predicate, &mut ret);
+    ret
+}
+
+fn collect_column_bounds(predicate: &Expr, bounds: &mut Vec<(String, ColumnBound)>) {
+    match predicate {
+        Expr::Nested(inner) => collect_column_bounds(inner, bounds),
+        Expr::BinaryOp { left, op: BinaryOperator::And, right } => {
+            collect_column_bounds(left, bounds);
+            collect_column_bounds(right, bounds);
+        },
+        Expr::BinaryOp { left, op, right } => {
+            let (c_name, value, op) = match (column_name(left), column_name(right)) {
+                (Some(c_name), None) => (c_name, constant(right), op.clone()),
+                (None, Some(c_name)) => (c_name, constant(left), match op {
+                    BinaryOperator::Lt => BinaryOperator::Gt,
+                    BinaryOperator::LtEq => BinaryOperator::GtEq,
+                    BinaryOperator::Gt => BinaryOperator::Lt,
+                    BinaryOperator::GtEq => BinaryOperator::LtEq,
+                    op => op.clone(),
+                }),
+                _ => return,
+            };
+            let Some(value) = value else {
+                return;
+            };
+            let bound = match op {
+                BinaryOperator::Eq => ColumnBound::OneOf(vec![value]),
+                BinaryOperator::Lt => ColumnBound::Range(Bound::Unbounded, Bound::Excluded(value)),
+                BinaryOperator::LtEq => ColumnBound::Range(Bound::Unbounded, Bound::Included(value)),
+                BinaryOperator::Gt => ColumnBound::Range(Bound::Excluded(value), Bound::Unbounded),
+                BinaryOperator::GtEq => ColumnBound::Range(Bound::Included(value), Bound::Unbounded),
+                _ => return,
+            };
+            bounds.push((c_name, bound));
+        },
+        Expr::InList { expr, list, negated: false } => {
+            if let Some(c_name) = column_name(expr) {
+                let values: Option<Vec<Value>> = list.iter().map(constant).collect();
+                if let Some(values) = values {
+                    bounds.push((c_name, ColumnBound::OneOf(values)));
+                }
+            }
+        },
+        Expr::Between { expr, negated: false, low, high } => {
+            if let (Some(c_name), Some(low), Some(high)) = (column_name(expr), constant(low), constant(high)) {
+                bounds.push((c_name, ColumnBound::Range(Bound::Included(low), Bound::Included(high))));
+            }
+        },
+        _ => {}
+    }
+}
+
+fn column_name(expr: &Expr) -> Option<String> {
+    match expr {
+        Expr::Identifier(ident) => Some(ident.value.clone()),
+        Expr::CompoundIdentifier(idents) => idents.last().map(|ident| ident.value.clone()),
+        Expr::Nested(inner) => column_name(inner),
+        _ => None,
+    }
+}
+
+/// Value of `expr` if it does not depend on the row, NULL excluded since it never compares equal.
+fn constant(expr: &Expr) -> Option<Value> {
+    eval_expr(expr, &EmptyRow).ok().filter(|value| !value.is_null())
+}
@@ -1,7 +1,16 @@
-mod data_descriptor;
-mod executor;
-mod validators;
-mod raw_inner_value;
+pub mod data_descriptor;
+pub mod executor;
+pub mod validators;
+pub mod raw_inner_value;
+pub mod error;
+pub mod value;
+pub mod expression;
+pub mod query;
+pub mod index;
+pub mod aggregate;
+pub mod relation;
+pub mod window;
+pub mod functions;
 
 pub fn add(left: usize, right: usize) -> usize {
     left + right
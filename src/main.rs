@@ -1,11 +1,7 @@
 use sqlparser::dialect::GenericDialect;
 use sqlparser::parser::Parser;
-mod data_descriptor;
-mod executor;
-mod validators;
-mod raw_inner_value;
-use crate::executor::{Execute, SuccessStatus};
-use crate::data_descriptor::DataBase;
+use in_memory_db::executor::{Execute, SuccessStatus};
+use in_memory_db::data_descriptor::DataBase;
 use std::time::Instant;
 fn main() {
     let rows_to_insert = 1000000;
@@ -29,6 +25,7 @@ fn main() {
         }));
     }
     sql.push_str(" SELECT * from users;");
+    sql.push_str(&format!(" SELECT * from users WHERE id = {};", rows_to_insert / 2));
     //println!("{}", sql);
 
     let dialect = GenericDialect {}; // or AnsiDialect, or your own dialect ...
@@ -0,0 +1,956 @@
+use std::cell::{Cell, RefCell};
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::ops::ControlFlow;
+use std::rc::Rc;
+use std::sync::MutexGuard;
+use sqlparser::ast::{Cte, Distinct, Expr, Function, GroupByExpr, ObjectName, OrderByExpr, Query, Select, SelectItem, SetExpr, SetOperator, SetQuantifier,
+    TableAlias, TableFactor, TableWithJoins, Value as SqlValue, Visit, Visitor};
+use crate::aggregate::{Accumulator, collect_aggregates};
+use crate::data_descriptor::{DataBase, SortColumn, TableInfo};
+use crate::error::DbError;
+use crate::executor::{ResultSet, table_name_of};
+use crate::expression::{ColumnLookup, EmptyRow, eval_expr, eval_predicate};
+use crate::relation::{QualifiedRow, Relation, RelationColumn, RelationRow, RowLayout, check_qualifiers, load_from};
+use crate::value::Value;
+use crate::window::{WindowScope, Windows};
+
+/// Name of the output column produced by an unaliased projection.
+fn projection_name(expr: &Expr) -> String {
+    match expr {
+        Expr::Identifier(ident) => ident.value.clone(),
+        Expr::CompoundIdentifier(idents) => idents.last().map(|ident| ident.value.clone()).unwrap_or_default(),
+        _ => expr.to_string(),
+    }
+}
+
+/// Where the value of an ORDER BY or DISTINCT ON key comes from.
+enum SortSource<'a> {
+    /// An output column, named by its alias or its 1-based position.
+    Output(usize),
+    /// An expression over the source row.
+    Expr(&'a Expr),
+}
+
+impl<'a> SortSource<'a> {
+    fn new(expr: &'a Expr, columns: &[String], clause: &str) -> Result<Self, DbError> {
+        Ok(match expr {
+            Expr::Value(SqlValue::Number(position, _)) => {
+                match position.parse::<usize>() {
+                    Ok(position) if position >= 1 && position <= columns.len() => SortSource::Output(position - 1),
+                    _ => { return Err(DbError::Parse(format!("{} position {} is not in select list", clause, position))); }
+                }
+            },
+            Expr::Identifier(ident) => match columns.iter().position(|c_name| *c_name == ident.value) {
+                Some(position) => SortSource::Output(position),
+                None => SortSource::Expr(expr),
+            },
+            expr => SortSource::Expr(expr),
+        })
+    }
+
+    fn value(&self, row_values: &[Value], row: &dyn ColumnLookup) -> Result<Value, DbError> {
+        match self {
+            SortSource::Output(position) => Ok(row_values[*position].clone()),
+            SortSource::Expr(expr) => eval_expr(expr, row),
+        }
+    }
+}
+
+/// One ORDER BY key, NULLs sorting last ascending and first descending unless told otherwise.
+pub(crate) struct SortKey<'a> {
+    source: SortSource<'a>,
+    pub(crate) descending: bool,
+    pub(crate) nulls_first: bool,
+}
+
+impl<'a> SortKey<'a> {
+    fn new(order_by: &'a OrderByExpr, columns: &[String]) -> Result<Self, DbError> {
+        let source = SortSource::new(&order_by.expr, columns, "ORDER BY")?;
+        let descending = order_by.asc == Some(false);
+        Ok(Self {
+            source,
+            descending,
+            nulls_first: order_by.nulls_first.unwrap_or(descending),
+        })
+    }
+
+    /// A key of a window ORDER BY, which has no output columns to name and always sorts on its expression.
+    pub(crate) fn of_window(order_by: &'a OrderByExpr) -> Self {
+        let descending = order_by.asc == Some(false);
+        Self {
+            source: SortSource::Expr(&order_by.expr),
+            descending,
+            nulls_first: order_by.nulls_first.unwrap_or(descending),
+        }
+    }
+}
+
+/// Orders two rows by their sort key values.
+pub(crate) fn compare_sort_values(l_values: &[Value], r_values: &[Value], sort_keys: &[SortKey]) -> Ordering {
+    for ((l_value, r_value), sort_key) in l_values.iter().zip(r_values).zip(sort_keys) {
+        let ordering = match (l_value.is_null(), r_value.is_null()) {
+            (true, true) => Ordering::Equal,
+            (true, false) => if sort_key.nulls_first { Ordering::Less } else { Ordering::Greater },
+            (false, true) => if sort_key.nulls_first { Ordering::Greater } else { Ordering::Less },
+            (false, false) if sort_key.descending => l_value.cmp(r_value).reverse(),
+            (false, false) => l_value.cmp(r_value),
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    Ordering::Equal
+}
+
+/// An output row with the values it is sorted and deduplicated on.
+struct CollectedRow {
+    sort_values: Vec<Value>,
+    distinct_key: Vec<Value>,//DISTINCT ON values
+    row_values: Vec<Value>,
+}
+
+/// A row being ranked by the top-N heap, `position` keeping equal rows in scan order.
+struct RankedRow<'a> {
+    row: CollectedRow,
+    position: usize,
+    sort_keys: &'a [SortKey<'a>],
+}
+
+impl Ord for RankedRow<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        compare_sort_values(&self.row.sort_values, &other.row.sort_values, self.sort_keys).then(self.position.cmp(&other.position))
+    }
+}
+
+impl PartialOrd for RankedRow<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for RankedRow<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for RankedRow<'_> {}
+
+/// Rows kept by LIMIT, OFFSET and FETCH FIRST.
+struct Pagination {
+    offset: usize,
+    limit: Option<usize>,
+    with_ties: bool,
+}
+
+impl Pagination {
+    fn new(query: &Query) -> Result<Self, DbError> {
+        if query.limit.is_some() && query.fetch.is_some() {
+            return Err(DbError::Parse("LIMIT and FETCH cannot be used together".to_string()));
+        }
+        let mut limit = query.limit.as_ref().map(|limit| row_count(limit, "LIMIT")).transpose()?;
+        let mut with_ties = false;
+        if let Some(fetch) = &query.fetch {
+            if fetch.percent {
+                return Err(DbError::Unsupported("FETCH ... PERCENT".to_string()));
+            }
+            if fetch.with_ties && query.order_by.is_empty() {
+                return Err(DbError::Parse("FETCH ... WITH TIES requires ORDER BY".to_string()));
+            }
+            limit = Some(fetch.quantity.as_ref().map(|quantity| row_count(quantity, "FETCH")).transpose()?.unwrap_or(1));
+            with_ties = fetch.with_ties;
+        }
+        Ok(Self {
+            offset: query.offset.as_ref().map(|offset| row_count(&offset.value, "OFFSET")).transpose()?.unwrap_or(0),
+            limit,
+            with_ties,
+        })
+    }
+
+    /// Number of leading rows a query has to produce, None when it needs all of them.
+    fn max_rows(&self) -> Option<usize> {
+        match self.limit {
+            Some(limit) if !self.with_ties => Some(self.offset.saturating_add(limit)),
+            _ => None,
+        }
+    }
+}
+
+/// Value of the row count given to `clause`, which must be a non-negative integer.
+fn row_count(expr: &Expr, clause: &str) -> Result<usize, DbError> {
+    match eval_expr(expr, &EmptyRow)? {
+        Value::Int64(count) if count >= 0 => Ok(count as usize),
+        Value::UInt64(count) => Ok(count as usize),
+        value => Err(DbError::Execution(format!("{} must be a non-negative integer, got {}", clause, value))),
+    }
+}
+
+/// Most iterations of a recursive common table expression, so that a query whose rows never run out fails
+/// instead of running forever.
+const MAX_RECURSION: usize = 1000;
+
+/// Results of the common table expressions a query can read, later definitions shadowing earlier ones.
+#[derive(Debug, Clone, Default)]
+pub struct CommonTables {
+    tables: Vec<(String, Rc<ResultSet>)>,
+}
+
+impl CommonTables {
+    /// Result of the common table named `name`, which has to be a single identifier.
+    pub fn get(&self, name: &ObjectName) -> Option<&Rc<ResultSet>> {
+        match name.0.as_slice() {
+            [ident] => self.tables.iter().rev().find(|(table_name, _)| *table_name == ident.value).map(|(_, result)| result),
+            _ => None,
+        }
+    }
+
+    fn with(&self, name: &str, result: ResultSet) -> Self {
+        let mut tables = self.clone();
+        tables.tables.push((name.to_string(), Rc::new(result)));
+        tables
+    }
+}
+
+/// What a query sees of the queries around it: the current row of the enclosing query for a subquery,
+/// and the common table expressions in scope.
+#[derive(Clone, Copy)]
+pub struct Enclosing<'a> {
+    pub row: Option<&'a dyn ColumnLookup>,
+    pub tables: &'a CommonTables,
+}
+
+pub fn execute_query(db: &DataBase, query: &Query) -> Result<ResultSet, DbError> {
+    run_query(db, query, Enclosing { row: None, tables: &CommonTables::default() })
+}
+
+/// Runs `query`, a subquery when `enclosing` has a row.
+pub fn run_query(db: &DataBase, query: &Query, enclosing: Enclosing) -> Result<ResultSet, DbError> {
+    let mut tables = enclosing.tables.clone();
+    if let Some(with) = &query.with {
+        for cte in &with.cte_tables {
+            let scope = Enclosing { row: enclosing.row, tables: &tables };
+            let mut result = match &*cte.query.body {
+                SetExpr::SetOperation { op: SetOperator::Union, set_quantifier, left, right } if with.recursive && reads_table(right, &cte.alias.name.value) => {
+                    if !cte.query.order_by.is_empty() || cte.query.limit.is_some() || cte.query.offset.is_some() || cte.query.fetch.is_some() {
+                        return Err(DbError::Unsupported(format!("ORDER BY, LIMIT and OFFSET in recursive query {}", cte.alias.name)));
+                    }
+                    recursive_table(db, cte, left, right, *set_quantifier != SetQuantifier::All, scope)?
+                },
+                _ => run_query(db, &cte.query, scope)?,
+            };
+            rename_columns(&mut result, &cte.alias)?;
+            tables = tables.with(&cte.alias.name.value, result);
+        }
+    }
+    let enclosing = Enclosing { row: enclosing.row, tables: &tables };
+
+    let pagination = Pagination::new(query)?;
+    let (mut result, sort_values) = match &*query.body {
+        SetExpr::Select(select_q) => execute_select(db, select_q, &query.order_by, pagination.max_rows(), enclosing)?,
+        body => sort_result(run_body(db, body, enclosing)?, &query.order_by)?,
+    };
+
+    let skipped = pagination.offset.min(result.rows.len());
+    let mut kept = match pagination.limit {
+        Some(limit) => limit.min(result.rows.len() - skipped),
+        None => result.rows.len() - skipped,
+    };
+    if pagination.with_ties && kept > 0 {
+        let last = &sort_values[skipped + kept - 1];
+        kept += sort_values[skipped + kept..].iter().take_while(|values| *values == last).count();
+    }
+    result.rows.truncate(skipped + kept);
+    result.rows.drain(..skipped);
+    Ok(result)
+}
+
+/// Projects the rows of a SELECT along with their sort key values, keeping only the best `max_rows` when set.
+struct RowCollector<'a> {
+    projection: &'a [SelectItem],
+    wildcards: &'a [Vec<RelationColumn>],//columns expanded by each wildcard of `projection`
+    sort_keys: &'a [SortKey<'a>],
+    distinct: &'a RowDistinct<'a>,
+    seen_rows: HashSet<Vec<Value>>,//output rows already collected by a SELECT DISTINCT
+    top_rows: Option<(usize, BinaryHeap<RankedRow<'a>>)>,
+    rows: Vec<CollectedRow>,
+    pushed: usize,
+}
+
+/// Deduplication asked by SELECT DISTINCT or DISTINCT ON.
+enum RowDistinct<'a> {
+    All,
+    Distinct,
+    /// Keeps the first row, in ORDER BY order, of every set of rows with equal values.
+    On(Vec<SortSource<'a>>),
+}
+
+impl<'a> RowDistinct<'a> {
+    fn new(distinct: Option<&'a Distinct>, columns: &[String]) -> Result<Self, DbError> {
+        Ok(match distinct {
+            None => RowDistinct::All,
+            Some(Distinct::Distinct) => RowDistinct::Distinct,
+            Some(Distinct::On(exprs)) => RowDistinct::On(exprs.iter().map(|expr| SortSource::new(expr, columns, "DISTINCT ON")).collect::<Result<_, _>>()?),
+        })
+    }
+}
+
+impl<'a> RowCollector<'a> {
+    fn new(projection: &'a [SelectItem], wildcards: &'a [Vec<RelationColumn>], sort_keys: &'a [SortKey<'a>], distinct: &'a RowDistinct<'a>, max_rows: Option<usize>) -> Self {
+        // DISTINCT ON has to see every row in order before choosing the first of each set
+        let top_rows = max_rows.filter(|_| !sort_keys.is_empty() && !matches!(distinct, RowDistinct::On(_)));
+        Self {
+            projection,
+            wildcards,
+            sort_keys,
+            distinct,
+            seen_rows: HashSet::new(),
+            top_rows: top_rows.map(|max_rows| (max_rows, BinaryHeap::new())),
+            rows: Vec::new(),
+            pushed: 0,
+        }
+    }
+
+    fn push(&mut self, row: &dyn ColumnLookup) -> Result<(), DbError> {
+        let mut row_values: Vec<Value> = Vec::with_capacity(self.projection.len());
+        for (projection, wildcard) in self.projection.iter().zip(self.wildcards) {
+            match projection {
+                SelectItem::Wildcard(_) | SelectItem::QualifiedWildcard(_, _) => {
+                    for column in wildcard {
+                        row_values.push(row.lookup(column.relation.as_deref(), &column.name)?);
+                    }
+                },
+                SelectItem::UnnamedExpr(expr) | SelectItem::ExprWithAlias { expr, .. } => {
+                    row_values.push(eval_expr(expr, row)?);
+                },
+            }
+        }
+        if let RowDistinct::Distinct = self.distinct {
+            if self.seen_rows.contains(&row_values) {
+                return Ok(());
+            }
+            self.seen_rows.insert(row_values.clone());
+        }
+        let mut sort_values = Vec::with_capacity(self.sort_keys.len());
+        for sort_key in self.sort_keys {
+            sort_values.push(sort_key.source.value(&row_values, row)?);
+        }
+        let mut distinct_key = Vec::new();
+        if let RowDistinct::On(sources) = self.distinct {
+            for source in sources {
+                distinct_key.push(source.value(&row_values, row)?);
+            }
+        }
+        let collected = CollectedRow { sort_values, distinct_key, row_values };
+        match &mut self.top_rows {
+            Some((max_rows, heap)) => {
+                heap.push(RankedRow { row: collected, position: self.pushed, sort_keys: self.sort_keys });
+                if heap.len() > *max_rows {
+                    heap.pop();
+                }
+            },
+            None => self.rows.push(collected),
+        }
+        self.pushed += 1;
+        Ok(())
+    }
+
+    /// The collected rows in ORDER BY order, `ordered` telling that they were pushed in that order.
+    fn finish(mut self, ordered: bool) -> Vec<CollectedRow> {
+        if let Some((_, heap)) = self.top_rows {
+            self.rows.extend(heap.into_sorted_vec().into_iter().map(|ranked| ranked.row));
+        } else if !ordered && !self.sort_keys.is_empty() {
+            let sort_keys = self.sort_keys;
+            self.rows.sort_by(|l_row, r_row| compare_sort_values(&l_row.sort_values, &r_row.sort_values, sort_keys));
+        }
+        if let RowDistinct::On(_) = self.distinct {
+            let mut seen_keys: HashSet<Vec<Value>> = HashSet::new();
+            self.rows.retain(|row| seen_keys.insert(row.distinct_key.clone()));
+        }
+        self.rows
+    }
+}
+
+/// Rows of a query body that has no ORDER BY or LIMIT of its own.
+fn run_body(db: &DataBase, body: &SetExpr, enclosing: Enclosing) -> Result<ResultSet, DbError> {
+    match body {
+        SetExpr::Select(select_q) => Ok(execute_select(db, select_q, &[], None, enclosing)?.0),
+        SetExpr::Query(query) => run_query(db, query, enclosing),
+        SetExpr::SetOperation { op, set_quantifier, left, right } => {
+            let distinct = match set_quantifier {
+                SetQuantifier::All => false,
+                SetQuantifier::Distinct | SetQuantifier::None => true,
+                _ => { return Err(DbError::Unsupported(format!("{} {}", op, set_quantifier))); }
+            };
+            combine_results(run_body(db, left, enclosing)?, run_body(db, right, enclosing)?, op, distinct)
+        },
+        _ => Err(DbError::Unsupported(format!("query {}", body))),
+    }
+}
+
+/// Rows of `left op right`, named after the columns of `left`. Rows are compared as a whole, NULLs
+/// equal to each other; with `distinct` every row appears once, otherwise as many times as the operator keeps it.
+fn combine_results(mut left: ResultSet, right: ResultSet, op: &SetOperator, distinct: bool) -> Result<ResultSet, DbError> {
+    if left.columns.len() != right.columns.len() {
+        return Err(DbError::Execution(format!("each {} query must have the same number of columns, got {} and {}", op, left.columns.len(), right.columns.len())));
+    }
+    // every value of a column, from either side, has to be compatible with its first one
+    for position in 0..left.columns.len() {
+        let mut values = left.rows.iter().chain(&right.rows).map(|row| &row[position]).filter(|value| !value.is_null());
+        if let Some(first_value) = values.next() {
+            if let Some(value) = values.find(|value| !value.is_compatible_with(first_value)) {
+                return Err(DbError::Execution(format!("{} column {} mixes incompatible values {} and {}", op, left.columns[position], first_value, value)));
+            }
+        }
+    }
+
+    let mut right_counts: HashMap<Vec<Value>, usize> = HashMap::new();
+    if !matches!(op, SetOperator::Union) {
+        for row in &right.rows {
+            *right_counts.entry(row.clone()).or_default() += 1;
+        }
+    }
+    let mut rows = match op {
+        SetOperator::Union => {
+            left.rows.extend(right.rows);
+            left.rows
+        },
+        // with ALL, each right row cancels a single equal left row
+        SetOperator::Intersect | SetOperator::Except => {
+            let intersect = matches!(op, SetOperator::Intersect);
+            left.rows.into_iter().filter(|row| {
+                let in_right = match right_counts.get_mut(row) {
+                    Some(count) if *count > 0 => {
+                        if !distinct {
+                            *count -= 1;
+                        }
+                        true
+                    },
+                    _ => false,
+                };
+                in_right == intersect
+            }).collect()
+        },
+    };
+    if distinct {
+        let mut seen_rows: HashSet<Vec<Value>> = HashSet::new();
+        rows.retain(|row| seen_rows.insert(row.clone()));
+    }
+    Ok(ResultSet { columns: left.columns, rows })
+}
+
+/// `result` sorted by `order_by`, whose keys name output columns, along with the sort key values of each row.
+fn sort_result(result: ResultSet, order_by: &[OrderByExpr]) -> Result<(ResultSet, Vec<Vec<Value>>), DbError> {
+    let sort_keys = order_by.iter().map(|order_by| SortKey::new(order_by, &result.columns)).collect::<Result<Vec<_>, _>>()?;
+    let layout = RowLayout::unqualified(&result.columns);
+    let mut rows = Vec::with_capacity(result.rows.len());
+    for row_values in result.rows {
+        let row = RelationRow { layout: &layout, values: &row_values };
+        let sort_values = sort_keys.iter().map(|sort_key| sort_key.source.value(&row_values, &row)).collect::<Result<Vec<_>, _>>()?;
+        rows.push((sort_values, row_values));
+    }
+    rows.sort_by(|(l_values, _), (r_values, _)| compare_sort_values(l_values, r_values, &sort_keys));
+    let (sort_values, rows) = rows.into_iter().unzip();
+    Ok((ResultSet { columns: result.columns, rows }, sort_values))
+}
+
+/// Names the columns of a common table expression after its alias, when the alias lists them.
+fn rename_columns(result: &mut ResultSet, alias: &TableAlias) -> Result<(), DbError> {
+    if alias.columns.is_empty() {
+        return Ok(());
+    }
+    if alias.columns.len() != result.columns.len() {
+        return Err(DbError::Parse(format!("{} has {} columns but its alias names {}", alias.name, result.columns.len(), alias.columns.len())));
+    }
+    result.columns = alias.columns.iter().map(|ident| ident.value.clone()).collect();
+    Ok(())
+}
+
+/// Whether `body` reads the table or common table expression `table_name`.
+fn reads_table(body: &SetExpr, table_name: &str) -> bool {
+    struct TableFinder<'a>(&'a str);
+    impl Visitor for TableFinder<'_> {
+        type Break = ();
+
+        fn pre_visit_relation(&mut self, relation: &ObjectName) -> ControlFlow<Self::Break> {
+            match relation.0.as_slice() {
+                [ident] if ident.value == self.0 => ControlFlow::Break(()),
+                _ => ControlFlow::Continue(()),
+            }
+        }
+    }
+    body.visit(&mut TableFinder(table_name)).is_break()
+}
+
+/// Rows of `WITH RECURSIVE cte AS (anchor UNION [ALL] step)`: the anchor rows, then the rows `step` produces
+/// while `cte` holds the rows of the previous iteration, until an iteration adds no row.
+/// With `distinct`, rows already produced are dropped, which also ends the iteration over cyclic data.
+fn recursive_table(db: &DataBase, cte: &Cte, anchor: &SetExpr, step: &SetExpr, distinct: bool, enclosing: Enclosing) -> Result<ResultSet, DbError> {
+    let mut result = run_body(db, anchor, enclosing)?;
+    rename_columns(&mut result, &cte.alias)?;
+    let mut seen_rows: HashSet<Vec<Value>> = HashSet::new();
+    if distinct {
+        result.rows.retain(|row| seen_rows.insert(row.clone()));
+    }
+    let mut working_rows = result.rows.clone();
+    let mut iterations = 0;
+    while !working_rows.is_empty() {
+        iterations += 1;
+        if iterations > MAX_RECURSION {
+            return Err(DbError::Execution(format!("recursive query {} did not end after {} iterations", cte.alias.name, MAX_RECURSION)));
+        }
+        let working_table = ResultSet { columns: result.columns.clone(), rows: working_rows };
+        let tables = enclosing.tables.with(&cte.alias.name.value, working_table);
+        let mut step_result = run_body(db, step, Enclosing { row: enclosing.row, tables: &tables })?;
+        if step_result.columns.len() != result.columns.len() {
+            return Err(DbError::Execution(format!("recursive query {} returns {} columns but its anchor returns {}", cte.alias.name, step_result.columns.len(), result.columns.len())));
+        }
+        if distinct {
+            step_result.rows.retain(|row| seen_rows.insert(row.clone()));
+        }
+        result.rows.extend(step_result.rows.iter().cloned());
+        working_rows = step_result.rows;
+    }
+    Ok(result)
+}
+
+/// What the rows of a query see beyond their own columns: the row of the enclosing query for a subquery,
+/// and the database its own subqueries run against.
+struct QueryContext<'a> {
+    db: &'a DataBase,
+    enclosing: Enclosing<'a>,
+    results: RefCell<HashMap<*const Query, Rc<ResultSet>>>,//results of the uncorrelated subqueries, run once
+}
+
+impl ColumnLookup for QueryContext<'_> {
+    fn lookup(&self, qualifier: Option<&str>, name: &str) -> Result<Value, DbError> {
+        match self.enclosing.row {
+            Some(outer) => outer.lookup(qualifier, name),
+            None => Err(DbError::UnknownColumn(name.to_string())),
+        }
+    }
+
+    fn run_subquery(&self, query: &Query, row: &dyn ColumnLookup) -> Result<Rc<ResultSet>, DbError> {
+        if let Some(result) = self.results.borrow().get(&(query as *const Query)) {
+            return Ok(result.clone());
+        }
+        let outer = OuterRow { row, read: Cell::new(false) };
+        let result = Rc::new(run_query(self.db, query, Enclosing { row: Some(&outer), tables: self.enclosing.tables })?);
+        // a subquery that never read the current row gives the same result for every row
+        if !outer.read.get() {
+            self.results.borrow_mut().insert(query, result.clone());
+        }
+        Ok(result)
+    }
+}
+
+/// Runs `f` with the scope the subqueries in `exprs`, the expressions of an UPDATE or DELETE on `table_name`, run in;
+/// `relation` is the name or alias their correlated column references qualify the changed row by.
+/// The statement keeps its table locked while it evaluates them, so when there are subqueries they read
+/// a copy of the table taken before the statement instead.
+pub fn with_statement_scope<'e, T>(db: &DataBase, table_name: &str, relation: &str, exprs: impl IntoIterator<Item = &'e Expr>,
+    f: impl FnOnce(&dyn ColumnLookup) -> Result<T, DbError>) -> Result<T, DbError> {
+    if !exprs.into_iter().any(|expr| expr.visit(&mut SubqueryFinder).is_break()) {
+        return f(&EmptyRow);
+    }
+    let snapshot = {
+        let table = db.get_table_ref(table_name).ok_or_else(|| DbError::UnknownTable(table_name.to_string()))?.lock().unwrap();
+        let rows = table.scan(None)?.iter().map(|scope| scope.values().to_vec()).collect();
+        ResultSet { columns: table.column_names().to_vec(), rows }
+    };
+    let tables = CommonTables::default().with(table_name, snapshot);
+    let context = StatementContext {
+        relation,
+        context: QueryContext { db, enclosing: Enclosing { row: None, tables: &tables }, results: RefCell::new(HashMap::new()) },
+    };
+    f(&context)
+}
+
+/// Runs the subqueries of an UPDATE or DELETE, whose changed row is qualified by `relation`.
+struct StatementContext<'a> {
+    relation: &'a str,
+    context: QueryContext<'a>,
+}
+
+impl ColumnLookup for StatementContext<'_> {
+    fn lookup(&self, _qualifier: Option<&str>, name: &str) -> Result<Value, DbError> {
+        Err(DbError::UnknownColumn(name.to_string()))
+    }
+
+    fn run_subquery(&self, query: &Query, row: &dyn ColumnLookup) -> Result<Rc<ResultSet>, DbError> {
+        self.context.run_subquery(query, &QualifiedRow { relation: self.relation, row })
+    }
+}
+
+/// Row of the enclosing query seen by a subquery, noting whether the subquery read it.
+struct OuterRow<'a> {
+    row: &'a dyn ColumnLookup,
+    read: Cell<bool>,
+}
+
+impl ColumnLookup for OuterRow<'_> {
+    fn lookup(&self, qualifier: Option<&str>, name: &str) -> Result<Value, DbError> {
+        self.read.set(true);
+        self.row.lookup(qualifier, name)
+    }
+}
+
+/// Row of a query, whose unknown columns are looked up in the row of the enclosing query.
+struct ScopedRow<'a, R: ColumnLookup> {
+    row: R,
+    context: &'a QueryContext<'a>,
+}
+
+impl<R: ColumnLookup> ColumnLookup for ScopedRow<'_, R> {
+    fn lookup(&self, qualifier: Option<&str>, name: &str) -> Result<Value, DbError> {
+        match self.row.lookup(qualifier, name) {
+            Err(DbError::UnknownColumn(c_name)) if self.context.enclosing.row.is_some() => {
+                self.context.lookup(qualifier, name).map_err(|_| DbError::UnknownColumn(c_name))
+            },
+            ret => ret,
+        }
+    }
+
+    fn precomputed(&self, expr: &Expr) -> Option<Value> {
+        self.row.precomputed(expr)
+    }
+
+    fn run_subquery(&self, query: &Query, row: &dyn ColumnLookup) -> Result<Rc<ResultSet>, DbError> {
+        self.context.run_subquery(query, row)
+    }
+}
+
+/// Visitor stopping at the first subquery.
+struct SubqueryFinder;
+
+impl Visitor for SubqueryFinder {
+    type Break = ();
+
+    fn pre_visit_query(&mut self, _query: &Query) -> ControlFlow<Self::Break> {
+        ControlFlow::Break(())
+    }
+}
+
+/// Whether the clauses of a SELECT hold a subquery, FROM included.
+fn has_subquery(select_q: &Select, order_by: &[OrderByExpr]) -> bool {
+    select_q.visit(&mut SubqueryFinder).is_break() || order_by.iter().any(|order_by| order_by.expr.visit(&mut SubqueryFinder).is_break())
+}
+
+/// Rows for which `selection` evaluates to TRUE, every row without it.
+fn filter_rows<R: ColumnLookup>(rows: impl Iterator<Item = R>, selection: Option<&Expr>) -> Result<Vec<R>, DbError> {
+    let mut ret = Vec::new();
+    for row in rows {
+        if let Some(selection) = selection {
+            if !eval_predicate(selection, &row)? {
+                continue;
+            }
+        }
+        ret.push(row);
+    }
+    Ok(ret)
+}
+
+/// Pushes `rows` to `collector`, as groups when the query aggregates.
+fn collect_rows<R: ColumnLookup>(rows: &[R], grouping: Option<&Grouping>, windows: Option<&Windows>, context: &QueryContext, collector: &mut RowCollector) -> Result<(), DbError> {
+    match grouping {
+        Some(grouping) => grouping.collect(rows, windows, context, collector),
+        None => push_rows(rows, windows, collector),
+    }
+}
+
+/// Pushes `rows` to `collector` along with the values the window calls take on them.
+fn push_rows<R: ColumnLookup>(rows: &[R], windows: Option<&Windows>, collector: &mut RowCollector) -> Result<(), DbError> {
+    let Some(windows) = windows else {
+        return rows.iter().try_for_each(|row| collector.push(row));
+    };
+    for (row, values) in rows.iter().zip(windows.evaluate(rows)?) {
+        collector.push(&WindowScope { row, windows, values })?;
+    }
+    Ok(())
+}
+
+/// Expressions of the SELECT list and of the ORDER BY keys that are not output columns.
+fn output_exprs<'a>(select_q: &'a Select, sort_keys: &'a [SortKey<'a>]) -> impl Iterator<Item = &'a Expr> {
+    let projected_exprs = select_q.projection.iter().filter_map(|projection| match projection {
+        SelectItem::UnnamedExpr(expr) | SelectItem::ExprWithAlias { expr, .. } => Some(expr),
+        _ => None,
+    });
+    let sort_exprs = sort_keys.iter().filter_map(|sort_key| match sort_key.source {
+        SortSource::Expr(expr) => Some(expr),
+        SortSource::Output(_) => None,
+    });
+    projected_exprs.chain(sort_exprs)
+}
+
+/// A group of rows seen as a single row: GROUP BY keys, and the columns they name read from one of its rows,
+/// aggregates computed over all of them.
+struct GroupScope<'a> {
+    row: Option<&'a dyn ColumnLookup>,//None for the empty group of an aggregate without GROUP BY
+    context: &'a QueryContext<'a>,
+    grouping: &'a Grouping<'a>,
+    group_values: Vec<Value>,
+    aggregate_values: Vec<Value>,
+}
+
+impl ColumnLookup for GroupScope<'_> {
+    fn lookup(&self, qualifier: Option<&str>, name: &str) -> Result<Value, DbError> {
+        let position = match self.grouping.layout.position(qualifier, name) {
+            Ok(position) => position,
+            // not a column of the grouped rows, maybe one of the enclosing query
+            Err(DbError::UnknownColumn(c_name)) => {
+                return match self.row {
+                    Some(row) => row.lookup(qualifier, name),
+                    None => self.context.lookup(qualifier, name).map_err(|_| DbError::UnknownColumn(c_name)),
+                };
+            },
+            Err(err) => return Err(err),
+        };
+        if !self.grouping.grouped_columns.contains(&position) {
+            let c_name = qualifier.map_or_else(|| name.to_string(), |qualifier| format!("{}.{}", qualifier, name));
+            return Err(DbError::Execution(format!("column {} must appear in the GROUP BY clause or be used in an aggregate function", c_name)));
+        }
+        match self.row {
+            Some(row) => row.lookup(qualifier, name),
+            None => Ok(Value::Null),
+        }
+    }
+
+    fn precomputed(&self, expr: &Expr) -> Option<Value> {
+        if let Some(position) = self.grouping.group_by.iter().position(|group_expr| *group_expr == expr) {
+            return Some(self.group_values[position].clone());
+        }
+        self.grouping.aggregates.iter().position(|aggregate| aggregate == expr).map(|position| self.aggregate_values[position].clone())
+    }
+
+    fn run_subquery(&self, query: &Query, row: &dyn ColumnLookup) -> Result<Rc<ResultSet>, DbError> {
+        self.context.run_subquery(query, row)
+    }
+}
+
+/// GROUP BY and aggregates of a SELECT.
+struct Grouping<'a> {
+    group_by: Vec<&'a Expr>,
+    aggregates: Vec<Expr>,
+    having: Option<&'a Expr>,
+    layout: &'a RowLayout,//columns of the rows being grouped
+    grouped_columns: Vec<usize>,//positions in `layout` of the columns GROUP BY names
+}
+
+impl<'a> Grouping<'a> {
+    /// None when the SELECT neither groups nor aggregates.
+    fn new(select_q: &'a Select, layout: &'a RowLayout, sort_keys: &[SortKey]) -> Result<Option<Self>, DbError> {
+        let source_columns = layout.visible_names();
+        let group_by_list = match &select_q.group_by {
+            GroupByExpr::Expressions(exprs) => exprs,
+            GroupByExpr::All => { return Err(DbError::Unsupported("GROUP BY ALL".to_string())); }
+        };
+        // GROUP BY may name an output column by its position or its alias
+        let mut group_by = Vec::with_capacity(group_by_list.len());
+        for expr in group_by_list {
+            let projected = match expr {
+                Expr::Value(SqlValue::Number(position, _)) => match position.parse::<usize>().ok().and_then(|position| select_q.projection.get(position.wrapping_sub(1))) {
+                    Some(SelectItem::UnnamedExpr(expr) | SelectItem::ExprWithAlias { expr, .. }) => expr,
+                    _ => { return Err(DbError::Parse(format!("GROUP BY position {} is not in select list", position))); }
+                },
+                Expr::Identifier(ident) if !source_columns.contains(&ident.value) => {
+                    select_q.projection.iter().find_map(|projection| match projection {
+                        SelectItem::ExprWithAlias { expr, alias } if alias.value == ident.value => Some(expr),
+                        _ => None,
+                    }).unwrap_or(expr)
+                },
+                _ => expr,
+            };
+            group_by.push(projected);
+        }
+        let aggregates = collect_aggregates(output_exprs(select_q, sort_keys).chain(select_q.having.as_ref()));
+        if group_by.is_empty() && aggregates.is_empty() && select_q.having.is_none() {
+            return Ok(None);
+        }
+        let grouped_columns = group_by.iter().filter_map(|expr| match expr {
+            Expr::Identifier(ident) => layout.position(None, &ident.value).ok(),
+            Expr::CompoundIdentifier(idents) => match idents.as_slice() {
+                [column] => layout.position(None, &column.value).ok(),
+                [.., qualifier, column] => layout.position(Some(&qualifier.value), &column.value).ok(),
+                [] => None,
+            },
+            _ => None,
+        }).collect();
+        Ok(Some(Self { group_by, aggregates, having: select_q.having.as_ref(), layout, grouped_columns }))
+    }
+
+    /// Hash aggregation of `rows`, pushing every group passing HAVING to `collector`.
+    fn collect<R: ColumnLookup>(&self, rows: &[R], windows: Option<&Windows>, context: &QueryContext, collector: &mut RowCollector) -> Result<(), DbError> {
+        let functions: Vec<&Function> = self.aggregates.iter().filter_map(|aggregate| match aggregate {
+            Expr::Function(function) => Some(function),
+            _ => None,
+        }).collect();
+        let new_accumulators = || functions.iter().map(|function| Accumulator::new(function)).collect::<Result<Vec<_>, _>>();
+        let mut group_positions: HashMap<Vec<Value>, usize> = HashMap::new();
+        let mut groups: Vec<(&R, Vec<Value>, Vec<Accumulator>)> = Vec::new();//first row of the group, group key, accumulators
+        for row in rows {
+            let group_key = self.group_by.iter().map(|expr| eval_expr(expr, row)).collect::<Result<Vec<_>, _>>()?;
+            let position = match group_positions.get(&group_key) {
+                Some(position) => *position,
+                None => {
+                    groups.push((row, group_key.clone(), new_accumulators()?));
+                    group_positions.insert(group_key, groups.len() - 1);
+                    groups.len() - 1
+                },
+            };
+            for accumulator in &mut groups[position].2 {
+                accumulator.update(row)?;
+            }
+        }
+
+        let mut scopes: Vec<GroupScope> = groups.into_iter().map(|(row, group_values, accumulators)| GroupScope {
+            row: Some(row as &dyn ColumnLookup),
+            context,
+            grouping: self,
+            group_values,
+            aggregate_values: accumulators.iter().map(Accumulator::finish).collect(),
+        }).collect();
+        // aggregates without GROUP BY return one row even for no input
+        if scopes.is_empty() && self.group_by.is_empty() {
+            scopes.push(GroupScope {
+                row: None,
+                context,
+                grouping: self,
+                group_values: Vec::new(),
+                aggregate_values: new_accumulators()?.iter().map(Accumulator::finish).collect(),
+            });
+        }
+        if let Some(having) = self.having {
+            let mut passing = Vec::with_capacity(scopes.len());
+            for scope in scopes {
+                if eval_predicate(having, &scope)? {
+                    passing.push(scope);
+                }
+            }
+            scopes = passing;
+        }
+        push_rows(&scopes, windows, collector)
+    }
+}
+
+/// Where the rows of a SELECT come from.
+enum RowSource<'a> {
+    /// No FROM clause.
+    Empty,
+    /// A single table, read through its indexes, along with the name it is referred to by.
+    /// Its lock is held until the query ends, so queries with subqueries materialize their tables instead.
+    Table(MutexGuard<'a, TableInfo>, String),
+    /// Joined tables or the product of several FROM items, materialized.
+    Joined(Relation),
+}
+
+impl<'a> RowSource<'a> {
+    fn new(db: &'a DataBase, select_q: &Select, order_by: &[OrderByExpr], enclosing: Enclosing) -> Result<Self, DbError> {
+        match select_q.from.as_slice() {
+            [] => Ok(RowSource::Empty),
+            [TableWithJoins { relation: TableFactor::Table { name, alias, args: None, .. }, joins }]
+                if joins.is_empty() && alias.as_ref().is_none_or(|alias| alias.columns.is_empty())
+                    && enclosing.tables.get(name).is_none() && !has_subquery(select_q, order_by) => {
+                let table_name_str = table_name_of(name)?;
+                let table = db.get_table_ref(&table_name_str).ok_or_else(|| DbError::UnknownTable(table_name_str.clone()))?;
+                let relation = alias.as_ref().map_or(table_name_str, |alias| alias.name.value.clone());
+                // qualifiers naming another table are left to the enclosing query
+                if let (Some(selection), None) = (&select_q.selection, enclosing.row) {
+                    check_qualifiers(selection, &relation)?;
+                }
+                Ok(RowSource::Table(table.lock().unwrap(), relation))
+            },
+            from => Ok(RowSource::Joined(load_from(db, from, select_q.selection.as_ref(), enclosing)?)),
+        }
+    }
+}
+
+/// Runs `select_q` sorted by `order_by`, along with the sort key values of each row.
+/// Only the first `max_rows` rows are guaranteed to be produced when it is set.
+pub fn execute_select(db: &DataBase, select_q: &Select, order_by: &[OrderByExpr], max_rows: Option<usize>, enclosing: Enclosing) -> Result<(ResultSet, Vec<Vec<Value>>), DbError> {
+    let source = RowSource::new(db, select_q, order_by, enclosing)?;
+    let layout = match &source {
+        RowSource::Empty => RowLayout::default(),
+        RowSource::Table(table_lock, relation) => RowLayout::of_table(relation, table_lock.column_names()),
+        RowSource::Joined(relation) => relation.layout.clone(),
+    };
+    let column_names = layout.visible_names();
+
+    let mut ret_value = ResultSet::default();
+    let mut wildcards: Vec<Vec<RelationColumn>> = Vec::with_capacity(select_q.projection.len());
+    let mut column_sources: Vec<Option<String>> = Vec::new();//table column each output column shows unchanged
+    for projection in &select_q.projection {
+        let wildcard = match projection {
+            SelectItem::Wildcard(_) => layout.wildcard(None)?,
+            SelectItem::QualifiedWildcard(name, _) => layout.wildcard(Some(&table_name_of(name)?))?,
+            _ => Vec::new(),
+        };
+        match projection {
+            SelectItem::Wildcard(_) | SelectItem::QualifiedWildcard(_, _) => {
+                ret_value.columns.extend(wildcard.iter().map(|column| column.name.clone()));
+                column_sources.extend(wildcard.iter().map(|column| Some(column.name.clone())));
+            },
+            SelectItem::UnnamedExpr(expr) => {
+                ret_value.columns.push(projection_name(expr));
+                column_sources.push(match expr {
+                    Expr::Identifier(ident) => column_names.iter().find(|c_name| **c_name == ident.value).cloned(),
+                    _ => None,
+                });
+            },
+            SelectItem::ExprWithAlias { alias, .. } => {
+                ret_value.columns.push(alias.value.clone());
+                column_sources.push(None);
+            },
+        }
+        wildcards.push(wildcard);
+    }
+
+    let sort_keys = order_by.iter().map(|order_by| SortKey::new(order_by, &ret_value.columns)).collect::<Result<Vec<_>, _>>()?;
+    let grouping = Grouping::new(select_q, &layout, &sort_keys)?;
+    let windows = Windows::new(select_q, output_exprs(select_q, &sort_keys))?;
+    let distinct = RowDistinct::new(select_q.distinct.as_ref(), &ret_value.columns)?;
+    let mut collector = RowCollector::new(&select_q.projection, &wildcards, &sort_keys, &distinct, max_rows);
+    let context = QueryContext { db, enclosing, results: RefCell::default() };
+    let mut ordered = false;
+    match &source {
+        RowSource::Table(table_lock, relation) => {
+            // the scan checks the WHERE clause against the table columns only, a subquery checks it here
+            let (scan_predicate, row_predicate) = match enclosing.row {
+                None => (select_q.selection.as_ref(), None),
+                Some(_) => (None, select_q.selection.as_ref()),
+            };
+            // sorting on plain columns can be left to an ordered index
+            let index_order: Option<Vec<SortColumn>> = sort_keys.iter().map(|sort_key| {
+                let c_name = match sort_key.source {
+                    SortSource::Output(position) => column_sources[position].as_deref()?,
+                    SortSource::Expr(Expr::Identifier(ident)) => column_names.iter().find(|c_name| **c_name == ident.value)?.as_str(),
+                    SortSource::Expr(_) => return None,
+                };
+                Some(SortColumn { name: c_name.to_string(), descending: sort_key.descending, nulls_first: sort_key.nulls_first })
+            }).collect::<Option<_>>().filter(|_| grouping.is_none());
+
+            // without ORDER BY, or when an index provides the order, the scan can stop after `max_rows`
+            let scan_limit = max_rows.filter(|_| (sort_keys.is_empty() || index_order.is_some()) && grouping.is_none() && windows.is_none()
+                && row_predicate.is_none() && matches!(distinct, RowDistinct::All));
+            let (rows, rows_ordered) = table_lock.scan_ordered(scan_predicate, index_order.as_deref().unwrap_or(&[]), scan_limit)?;
+            let rows = filter_rows(rows.into_iter().map(|row| ScopedRow { row: QualifiedRow { relation, row }, context: &context }), row_predicate)?;
+            collect_rows(&rows, grouping.as_ref(), windows.as_ref(), &context, &mut collector)?;
+            ordered = rows_ordered;
+        },
+        RowSource::Joined(relation) => {
+            let rows = relation.rows.iter().map(|values| ScopedRow { row: RelationRow { layout: &relation.layout, values }, context: &context });
+            collect_rows(&filter_rows(rows, select_q.selection.as_ref())?, grouping.as_ref(), windows.as_ref(), &context, &mut collector)?;
+        },
+        RowSource::Empty => {
+            // without FROM, the SELECT list is evaluated over a single empty row
+            let rows = std::iter::once(ScopedRow { row: EmptyRow, context: &context });
+            collect_rows(&filter_rows(rows, select_q.selection.as_ref())?, grouping.as_ref(), windows.as_ref(), &context, &mut collector)?;
+        },
+    }
+
+    let mut sort_values_out: Vec<Vec<Value>> = Vec::new();
+    for row in collector.finish(ordered) {
+        sort_values_out.push(row.sort_values);
+        ret_value.rows.push(row.row_values);
+    }
+    Ok((ret_value, sort_values_out))
+}
@@ -1,4 +1,4 @@
-use sqlparser::ast::{ColumnDef, Expr, DataType, ReferentialAction, Value};
+use sqlparser::ast::Value;
 
 pub trait InnerRawValue {
     fn get_inner_raw_str(&self)->Option<String>;
@@ -24,4 +24,4 @@ impl InnerRawValue for Value {
             _=>None
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,476 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::ops::ControlFlow;
+use sqlparser::ast::{BinaryOperator, Expr, Ident, JoinConstraint, JoinOperator, Query, TableAlias, TableFactor, TableWithJoins, Visit, Visitor};
+use crate::data_descriptor::DataBase;
+use crate::error::DbError;
+use crate::executor::table_name_of;
+use crate::expression::{ColumnLookup, eval_expr, eval_predicate};
+use crate::query::{Enclosing, run_query};
+use crate::value::Value;
+
+/// A column of the rows produced by FROM.
+#[derive(Debug, Clone)]
+pub struct RelationColumn {
+    pub relation: Option<String>,//table name or alias, None for the columns merged by JOIN ... USING
+    pub name: String,
+    pub hidden: bool,//only reachable through its relation, as the join columns of each side of USING
+}
+
+/// Columns of the rows produced by FROM, resolving possibly qualified column references to positions.
+#[derive(Debug, Clone, Default)]
+pub struct RowLayout {
+    columns: Vec<RelationColumn>,
+}
+
+impl RowLayout {
+    pub fn of_table(relation: &str, column_names: &[String]) -> Self {
+        Self {
+            columns: column_names.iter().map(|c_name| RelationColumn { relation: Some(relation.to_string()), name: c_name.clone(), hidden: false }).collect(),
+        }
+    }
+
+    /// Layout of a query result, whose columns belong to no relation.
+    pub fn unqualified(column_names: &[String]) -> Self {
+        Self {
+            columns: column_names.iter().map(|c_name| RelationColumn { relation: None, name: c_name.clone(), hidden: false }).collect(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.columns.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.columns.is_empty()
+    }
+
+    /// Position of the column `name`, of relation `qualifier` when given.
+    pub fn position(&self, qualifier: Option<&str>, name: &str) -> Result<usize, DbError> {
+        let mut found = self.columns.iter().enumerate().filter(|(_, column)| column.name == name && match qualifier {
+            Some(qualifier) => column.relation.as_deref() == Some(qualifier),
+            None => !column.hidden,
+        }).map(|(position, _)| position);
+        match (found.next(), found.next()) {
+            (Some(position), None) => Ok(position),
+            (Some(_), Some(_)) => Err(DbError::Execution(format!("column reference {} is ambiguous", name))),
+            (None, _) => Err(DbError::UnknownColumn(match qualifier {
+                Some(qualifier) => format!("{}.{}", qualifier, name),
+                None => name.to_string(),
+            })),
+        }
+    }
+
+    /// Columns expanded by `*`, or by `qualifier.*` when given.
+    pub fn wildcard(&self, qualifier: Option<&str>) -> Result<Vec<RelationColumn>, DbError> {
+        let Some(qualifier) = qualifier else {
+            return Ok(self.columns.iter().filter(|column| !column.hidden).cloned().collect());
+        };
+        let columns: Vec<RelationColumn> = self.columns.iter().filter(|column| column.relation.as_deref() == Some(qualifier)).cloned().collect();
+        if columns.is_empty() {
+            return Err(DbError::UnknownTable(qualifier.to_string()));
+        }
+        Ok(columns)
+    }
+
+    /// Names of the columns reachable without a qualifier.
+    pub fn visible_names(&self) -> Vec<String> {
+        self.columns.iter().filter(|column| !column.hidden).map(|column| column.name.clone()).collect()
+    }
+
+    fn concat(&self, other: &RowLayout) -> RowLayout {
+        RowLayout { columns: self.columns.iter().chain(&other.columns).cloned().collect() }
+    }
+
+    /// Renames the relation, and its columns when the alias lists them.
+    fn aliased(mut self, alias: &TableAlias) -> Result<Self, DbError> {
+        if !alias.columns.is_empty() && alias.columns.len() != self.columns.len() {
+            return Err(DbError::Parse(format!("{} has {} columns but its alias names {}", alias.name, self.columns.len(), alias.columns.len())));
+        }
+        for (position, column) in self.columns.iter_mut().enumerate() {
+            column.relation = Some(alias.name.value.clone());
+            if let Some(c_name) = alias.columns.get(position) {
+                column.name = c_name.value.clone();
+            }
+        }
+        Ok(self)
+    }
+}
+
+/// Rows produced by a FROM item, materialized.
+#[derive(Debug, Default)]
+pub struct Relation {
+    pub layout: RowLayout,
+    pub rows: Vec<Vec<Value>>,
+}
+
+/// One row of a relation resolving column references through its layout.
+pub struct RelationRow<'a> {
+    pub layout: &'a RowLayout,
+    pub values: &'a [Value],
+}
+
+impl ColumnLookup for RelationRow<'_> {
+    fn lookup(&self, qualifier: Option<&str>, name: &str) -> Result<Value, DbError> {
+        Ok(self.values[self.layout.position(qualifier, name)?].clone())
+    }
+}
+
+/// Row of a single table, whose column references may be qualified by the table name or alias.
+pub struct QualifiedRow<'a, R: ColumnLookup> {
+    pub relation: &'a str,
+    pub row: R,
+}
+
+impl<R: ColumnLookup> ColumnLookup for QualifiedRow<'_, R> {
+    fn lookup(&self, qualifier: Option<&str>, name: &str) -> Result<Value, DbError> {
+        match qualifier {
+            Some(qualifier) if qualifier != self.relation => Err(DbError::UnknownColumn(format!("{}.{}", qualifier, name))),
+            _ => self.row.lookup(None, name),
+        }
+    }
+}
+
+/// Rows of a FROM list, the Cartesian product of its items. The equalities of the WHERE clause `selection`
+/// between an item and the ones before it are used to hash join them instead of pairing every row;
+/// `selection` still has to be applied to the result.
+/// `enclosing` holds the common table expressions in scope and the row read by the derived tables of a correlated subquery.
+pub fn load_from(db: &DataBase, from: &[TableWithJoins], selection: Option<&Expr>, enclosing: Enclosing) -> Result<Relation, DbError> {
+    let Some((first, others)) = from.split_first() else {
+        return Ok(Relation::default());
+    };
+    let mut relation = load_joined(db, first, enclosing)?;
+    for data_from in others {
+        let right = load_joined(db, data_from, enclosing)?;
+        let mut plan = JoinPlan::new(None, &relation.layout, &right.layout)?;
+        for conjunct in selection.map(conjuncts).unwrap_or_default() {
+            plan.add_key(conjunct, &relation.layout, &right.layout);
+        }
+        relation = execute_join(relation, right, plan, false, false)?;
+    }
+    Ok(relation)
+}
+
+/// Rows of a FROM item along with every table joined to it.
+fn load_joined(db: &DataBase, from: &TableWithJoins, enclosing: Enclosing) -> Result<Relation, DbError> {
+    let mut relation = load_relation(db, &from.relation, enclosing)?;
+    for join in &from.joins {
+        let right = load_relation(db, &join.relation, enclosing)?;
+        relation = join_relations(relation, right, &join.join_operator)?;
+    }
+    Ok(relation)
+}
+
+fn load_relation(db: &DataBase, factor: &TableFactor, enclosing: Enclosing) -> Result<Relation, DbError> {
+    let (relation, alias) = match factor {
+        TableFactor::Table { name, alias, args: None, .. } => {
+            let table_name_str = table_name_of(name)?;
+            // common table expressions shadow the tables of the database
+            if let Some(result) = enclosing.tables.get(name) {
+                (Relation { layout: RowLayout::of_table(&table_name_str, &result.columns), rows: result.rows.clone() }, alias)
+            } else {
+                let table = db.get_table_ref(&table_name_str).ok_or_else(|| DbError::UnknownTable(table_name_str.clone()))?;
+                let table_lock = table.lock().unwrap();
+                let layout = RowLayout::of_table(&table_name_str, table_lock.column_names());
+                let rows = table_lock.scan(None)?.into_iter().map(|row| row.values().to_vec()).collect();
+                (Relation { layout, rows }, alias)
+            }
+        },
+        TableFactor::Derived { lateral: false, subquery, alias } => {
+            let result = run_query(db, subquery, enclosing)?;
+            (Relation { layout: RowLayout::unqualified(&result.columns), rows: result.rows }, alias)
+        },
+        TableFactor::NestedJoin { table_with_joins, alias } => (load_joined(db, table_with_joins, enclosing)?, alias),
+        _ => { return Err(DbError::Unsupported(format!("FROM {}", factor))); }
+    };
+    match alias {
+        Some(alias) => Ok(Relation { layout: relation.layout.aliased(alias)?, rows: relation.rows }),
+        None => Ok(relation),
+    }
+}
+
+/// Value one side of the join is matched on.
+enum JoinKey<'a> {
+    Column(usize),
+    Expr(&'a Expr),
+}
+
+impl JoinKey<'_> {
+    fn value(&self, layout: &RowLayout, values: &[Value]) -> Result<Value, DbError> {
+        match self {
+            JoinKey::Column(position) => Ok(values[*position].clone()),
+            JoinKey::Expr(expr) => eval_expr(expr, &RelationRow { layout, values }),
+        }
+    }
+}
+
+/// How the rows of two relations are matched and combined.
+struct JoinPlan<'a> {
+    layout: RowLayout,
+    merged: Vec<(usize, usize)>,//left and right positions of the columns merged by USING, output first
+    keys: Vec<(JoinKey<'a>, JoinKey<'a>)>,//equalities between a left and a right value
+    residual: Vec<&'a Expr>,//ON conjuncts evaluated on the combined row
+}
+
+impl<'a> JoinPlan<'a> {
+    fn new(constraint: Option<&'a JoinConstraint>, left: &RowLayout, right: &RowLayout) -> Result<Self, DbError> {
+        let using: Vec<String> = match constraint {
+            Some(JoinConstraint::Using(idents)) => idents.iter().map(|ident| ident.value.clone()).collect(),
+            Some(JoinConstraint::Natural) => {
+                let right_names = right.visible_names();
+                left.visible_names().into_iter().filter(|c_name| right_names.contains(c_name)).collect()
+            },
+            Some(JoinConstraint::On(on)) => {
+                let mut plan = JoinPlan { layout: left.concat(right), merged: Vec::new(), keys: Vec::new(), residual: Vec::new() };
+                for conjunct in conjuncts(on) {
+                    if !plan.add_key(conjunct, left, right) {
+                        plan.residual.push(conjunct);
+                    }
+                }
+                return Ok(plan);
+            },
+            Some(JoinConstraint::None) | None => Vec::new(),
+        };
+
+        let mut layout = RowLayout::default();
+        let mut merged = Vec::with_capacity(using.len());
+        for c_name in &using {
+            let positions = (left.position(None, c_name)?, right.position(None, c_name)?);
+            layout.columns.push(RelationColumn { relation: None, name: c_name.clone(), hidden: false });
+            merged.push(positions);
+        }
+        let hide_merged = |column: &RelationColumn| RelationColumn { hidden: column.hidden || using.contains(&column.name), ..column.clone() };
+        layout.columns.extend(left.columns.iter().chain(&right.columns).map(hide_merged));
+        let keys = merged.iter().map(|(l_position, r_position)| (JoinKey::Column(*l_position), JoinKey::Column(*r_position))).collect();
+        Ok(JoinPlan { layout, merged, keys, residual: Vec::new() })
+    }
+
+    /// Matches rows through `conjunct` when it is an equality between a left and a right value.
+    fn add_key(&mut self, conjunct: &'a Expr, left: &RowLayout, right: &RowLayout) -> bool {
+        let Expr::BinaryOp { left: l_expr, op: BinaryOperator::Eq, right: r_expr } = conjunct else {
+            return false;
+        };
+        match (side_of(l_expr, left, right), side_of(r_expr, left, right)) {
+            (Some(Side::Left), Some(Side::Right)) => self.keys.push((JoinKey::Expr(l_expr), JoinKey::Expr(r_expr))),
+            (Some(Side::Right), Some(Side::Left)) => self.keys.push((JoinKey::Expr(r_expr), JoinKey::Expr(l_expr))),
+            _ => return false,
+        }
+        true
+    }
+
+    /// Output row of a left and a right row, either of them missing for the unmatched rows of an outer join.
+    fn combine(&self, left: Option<&[Value]>, right: Option<&[Value]>, left_width: usize, right_width: usize) -> Vec<Value> {
+        let mut values = Vec::with_capacity(self.layout.len());
+        for (l_position, r_position) in &self.merged {
+            let left_value = left.map_or(Value::Null, |left| left[*l_position].clone());
+            values.push(match (left_value, right) {
+                (Value::Null, Some(right)) => right[*r_position].clone(),
+                (left_value, _) => left_value,
+            });
+        }
+        match left {
+            Some(left) => values.extend_from_slice(left),
+            None => values.resize(values.len() + left_width, Value::Null),
+        }
+        match right {
+            Some(right) => values.extend_from_slice(right),
+            None => values.resize(values.len() + right_width, Value::Null),
+        }
+        values
+    }
+
+    fn accepts(&self, values: &[Value]) -> Result<bool, DbError> {
+        let row = RelationRow { layout: &self.layout, values };
+        for conjunct in &self.residual {
+            if !eval_predicate(conjunct, &row)? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+}
+
+/// Joins two relations, through a hash table on the right rows when the condition has equalities
+/// between both sides, by comparing every pair of rows otherwise.
+fn join_relations(left: Relation, right: Relation, operator: &JoinOperator) -> Result<Relation, DbError> {
+    let (constraint, keep_left, keep_right) = match operator {
+        JoinOperator::Inner(constraint) => (Some(constraint), false, false),
+        JoinOperator::LeftOuter(constraint) => (Some(constraint), true, false),
+        JoinOperator::RightOuter(constraint) => (Some(constraint), false, true),
+        JoinOperator::FullOuter(constraint) => (Some(constraint), true, true),
+        JoinOperator::CrossJoin => (None, false, false),
+        _ => { return Err(DbError::Unsupported("semi, anti and APPLY joins".to_string())); }
+    };
+    let plan = JoinPlan::new(constraint, &left.layout, &right.layout)?;
+    execute_join(left, right, plan, keep_left, keep_right)
+}
+
+fn execute_join(left: Relation, right: Relation, plan: JoinPlan, keep_left: bool, keep_right: bool) -> Result<Relation, DbError> {
+    let (left_width, right_width) = (left.layout.len(), right.layout.len());
+
+    let key_values = |values: &[Value], layout: &RowLayout, left_side: bool| -> Result<Option<Vec<Value>>, DbError> {
+        let mut key = Vec::with_capacity(plan.keys.len());
+        for (l_key, r_key) in &plan.keys {
+            let value = if left_side { l_key.value(layout, values)? } else { r_key.value(layout, values)? };
+            // NULL never equals anything
+            if value.is_null() {
+                return Ok(None);
+            }
+            key.push(value);
+        }
+        Ok(Some(key))
+    };
+    let left_keys = left.rows.iter().map(|values| key_values(values, &left.layout, true)).collect::<Result<Vec<_>, DbError>>()?;
+    let right_keys = right.rows.iter().map(|values| key_values(values, &right.layout, false)).collect::<Result<Vec<_>, DbError>>()?;
+
+    // a key whose values span several type families, like dates matched with text, can compare equal
+    // to values it does not hash like, so its rows are matched by comparing them instead
+    let hashed: Vec<bool> = (0..plan.keys.len()).map(|k_position| {
+        let mut values = left_keys.iter().chain(&right_keys).flatten().map(|key| &key[k_position]);
+        let first = values.next();
+        values.all(|value| first.is_some_and(|first| first.is_same_family(value)))
+    }).collect();
+    let hash_key = |key: &[Value]| -> Vec<Value> {
+        key.iter().zip(&hashed).filter(|(_, hashed)| **hashed).map(|(value, _)| value.clone()).collect()
+    };
+    let compared_keys_match = |l_key: &[Value], r_key: &[Value]| {
+        l_key.iter().zip(r_key).zip(&hashed)
+            .all(|((l_value, r_value), hashed)| *hashed || l_value.compare(r_value) == Some(Ordering::Equal))
+    };
+
+    let mut right_positions: HashMap<Vec<Value>, Vec<usize>> = HashMap::new();
+    let all_right: Vec<usize> = (0..right.rows.len()).collect();
+    if hashed.contains(&true) {
+        for (position, r_key) in right_keys.iter().enumerate() {
+            if let Some(key) = r_key {
+                right_positions.entry(hash_key(key)).or_default().push(position);
+            }
+        }
+    }
+
+    let mut rows = Vec::new();
+    let mut right_matched = vec![false; right.rows.len()];
+    for (l_values, l_key) in left.rows.iter().zip(&left_keys) {
+        let candidates: &[usize] = match l_key {
+            Some(key) if hashed.contains(&true) => right_positions.get(&hash_key(key)).map_or(&[], |positions| positions.as_slice()),
+            Some(_) => &all_right,
+            None => &[],
+        };
+        let mut matched = false;
+        for r_position in candidates {
+            match (l_key, &right_keys[*r_position]) {
+                (Some(l_key), Some(r_key)) if compared_keys_match(l_key, r_key) => {},
+                _ => continue,
+            }
+            let values = plan.combine(Some(l_values), Some(&right.rows[*r_position]), left_width, right_width);
+            if plan.accepts(&values)? {
+                matched = true;
+                right_matched[*r_position] = true;
+                rows.push(values);
+            }
+        }
+        if keep_left && !matched {
+            rows.push(plan.combine(Some(l_values), None, left_width, right_width));
+        }
+    }
+    if keep_right {
+        for (r_values, _) in right.rows.iter().zip(&right_matched).filter(|(_, matched)| !**matched) {
+            rows.push(plan.combine(None, Some(r_values), left_width, right_width));
+        }
+    }
+    Ok(Relation { layout: plan.layout, rows })
+}
+
+/// The AND-ed conjuncts of `expr`.
+fn conjuncts(expr: &Expr) -> Vec<&Expr> {
+    match expr {
+        Expr::BinaryOp { left, op: BinaryOperator::And, right } => {
+            let mut ret = conjuncts(left);
+            ret.extend(conjuncts(right));
+            ret
+        },
+        Expr::Nested(inner) => conjuncts(inner),
+        _ => vec![expr],
+    }
+}
+
+#[derive(PartialEq)]
+enum Side {
+    Left,
+    Right,
+}
+
+/// Collects the column references of an expression, leaving out those of nested subqueries.
+#[derive(Default)]
+struct ColumnReferences {
+    columns: Vec<(Option<Ident>, Ident)>,
+    has_subquery: bool,
+    query_depth: usize,
+}
+
+impl Visitor for ColumnReferences {
+    type Break = ();
+
+    fn pre_visit_query(&mut self, _query: &Query) -> ControlFlow<Self::Break> {
+        self.has_subquery = true;
+        self.query_depth += 1;
+        ControlFlow::Continue(())
+    }
+
+    fn post_visit_query(&mut self, _query: &Query) -> ControlFlow<Self::Break> {
+        self.query_depth -= 1;
+        ControlFlow::Continue(())
+    }
+
+    fn pre_visit_expr(&mut self, expr: &Expr) -> ControlFlow<Self::Break> {
+        if self.query_depth > 0 {
+            return ControlFlow::Continue(());
+        }
+        match expr {
+            Expr::Identifier(ident) => self.columns.push((None, ident.clone())),
+            Expr::CompoundIdentifier(idents) => match idents.as_slice() {
+                [column] => self.columns.push((None, column.clone())),
+                [.., qualifier, column] => self.columns.push((Some(qualifier.clone()), column.clone())),
+                [] => {},
+            },
+            _ => {},
+        }
+        ControlFlow::Continue(())
+    }
+}
+
+/// Checks that the qualified column references of `expr` name `relation`, which the indexed scan of a
+/// single table does not do.
+pub fn check_qualifiers(expr: &Expr, relation: &str) -> Result<(), DbError> {
+    let mut references = ColumnReferences::default();
+    let _ = expr.visit(&mut references);
+    for (qualifier, column) in references.columns {
+        if let Some(qualifier) = qualifier.filter(|qualifier| qualifier.value != relation) {
+            return Err(DbError::UnknownColumn(format!("{}.{}", qualifier.value, column.value)));
+        }
+    }
+    Ok(())
+}
+
+/// The side of a join whose columns `expr` reads, None when it reads both, neither or a subquery.
+fn side_of(expr: &Expr, left: &RowLayout, right: &RowLayout) -> Option<Side> {
+    let mut references = ColumnReferences::default();
+    let _ = expr.visit(&mut references);
+    if references.has_subquery || references.columns.is_empty() {
+        return None;
+    }
+    let mut side = None;
+    for (qualifier, column) in &references.columns {
+        let qualifier = qualifier.as_ref().map(|qualifier| qualifier.value.as_str());
+        let column_side = match (left.position(qualifier, &column.value), right.position(qualifier, &column.value)) {
+            (Ok(_), Err(_)) => Side::Left,
+            (Err(_), Ok(_)) => Side::Right,
+            _ => return None,
+        };
+        if side.as_ref().is_some_and(|side| *side != column_side) {
+            return None;
+        }
+        side = Some(column_side);
+    }
+    side
+}
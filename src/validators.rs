@@ -1,15 +1,16 @@
-use sqlparser::ast::{DataType, CharacterLength, Value, ExactNumberInfo};
+use sqlparser::ast::{DataType, CharacterLength, Value as SqlValue, ExactNumberInfo};
 use chrono::{ NaiveDate, NaiveDateTime, NaiveTime };
 use serde_json::{Value as JsonValue};
+use crate::value::{Decimal, Rounding, Value, decode_hex};
 
 const ABSOLUTE_CHAR_MAX:u64 =  536_870_912;
 
-fn validate_value_for_char(length_p:&Option<CharacterLength>, value: &Value, def_size:u64, max_size:u64) -> bool {
+fn validate_value_for_char(length_p:&Option<CharacterLength>, value: &SqlValue, def_size:u64, max_size:u64) -> Result<Value, String> {
     let length = if let Some(len) = length_p {
         match len {
             CharacterLength::IntegerLength {
                 length,
-                unit,
+                ..
             } => *length,
             CharacterLength::Max => max_size,
         }
@@ -17,20 +18,20 @@ fn validate_value_for_char(length_p:&Option<CharacterLength>, value: &Value, def
         def_size
     };
     match value {
-        Value::SingleQuotedString(inner_value) => {
-            (inner_value.len() as u64) <= length
-        },
-        Value::EscapedStringLiteral(inner_value) => {
-            (inner_value.len() as u64) <= length
-        },
-        Value::DoubleQuotedString(inner_value) => {
-            (inner_value.len() as u64) <= length
+        SqlValue::SingleQuotedString(inner_value) |
+        SqlValue::EscapedStringLiteral(inner_value) |
+        SqlValue::DoubleQuotedString(inner_value) => {
+            if (inner_value.len() as u64) <= length {
+                Ok(Value::Text(inner_value.clone()))
+            } else {
+                Err(format!("longer than {} characters", length))
+            }
         },
-        _=>{false}
+        _=>{Err("not a string".to_string())}
     }
 }
 
-fn validate_value_for_clob(len_p_u64:&Option<u64>, value: &Value, max_size:u64) -> bool {
+fn validate_value_for_clob(len_p_u64:&Option<u64>, value: &SqlValue) -> Result<Value, String> {
     if let Some(len) = len_p_u64 {
         validate_value_for_char(&None, value, *len, ABSOLUTE_CHAR_MAX)
     }else {
@@ -38,144 +39,117 @@ fn validate_value_for_clob(len_p_u64:&Option<u64>, value: &Value, max_size:u64)
     }
 }
 
-fn validate_value_for_binary(len_p_u64:&Option<u64>, value: &Value, def_size:u64) -> bool {
+fn check_bytes_length(bytes: Vec<u8>, length: u64) -> Result<Value, String> {
+    if (bytes.len() as u64) <= length {
+        Ok(Value::Bytes(bytes))
+    } else {
+        Err(format!("longer than {} bytes", length))
+    }
+}
+
+fn validate_value_for_binary(len_p_u64:&Option<u64>, value: &SqlValue, def_size:u64) -> Result<Value, String> {
     let length = if let Some(len) = len_p_u64 {
         *len
     }else {
         def_size
     };
-    match value {
-        Value::Number(inner_value, _) => {
-            (inner_value.len() as u64) <= length
-        },
-        Value::SingleQuotedString(inner_value) => {
-            (inner_value.len() as u64) <= length
-        },
-        Value::EscapedStringLiteral(inner_value) => {
-            (inner_value.len() as u64) <= length
-        },
-        Value::SingleQuotedByteStringLiteral(inner_value) => {
-            (inner_value.len() as u64) <= length
-        },
-        Value::DoubleQuotedByteStringLiteral(inner_value) => {
-            (inner_value.len() as u64) <= length
-        },
-        Value::RawStringLiteral(inner_value) => {
-            (inner_value.len() as u64) <= length
-        },
-        Value::HexStringLiteral(inner_value) => {
-            (inner_value.len() as u64) <= length
-        },
-        Value::DoubleQuotedString(inner_value) => {
-            (inner_value.len() as u64) <= length
-        },
-        Value::UnQuotedString(inner_value) => {
-            (inner_value.len() as u64) <= length
-        },
-        _=>{false}
-    }
+    let bytes = match value {
+        SqlValue::HexStringLiteral(inner_value) => decode_hex(inner_value).ok_or_else(|| "not a valid hex string".to_string())?,
+        SqlValue::Number(inner_value, _) |
+        SqlValue::SingleQuotedString(inner_value) |
+        SqlValue::EscapedStringLiteral(inner_value) |
+        SqlValue::SingleQuotedByteStringLiteral(inner_value) |
+        SqlValue::DoubleQuotedByteStringLiteral(inner_value) |
+        SqlValue::RawStringLiteral(inner_value) |
+        SqlValue::DoubleQuotedString(inner_value) |
+        SqlValue::UnQuotedString(inner_value) => inner_value.as_bytes().to_vec(),
+        _=>{return Err("not a binary literal".to_string());}
+    };
+    check_bytes_length(bytes, length)
 }
-fn validate_value_for_bytes(len_p_u64:&Option<u64>, value: &Value, def_size:u64) -> bool {
+fn validate_value_for_bytes(len_p_u64:&Option<u64>, value: &SqlValue, def_size:u64) -> Result<Value, String> {
     let length = if let Some(len) = len_p_u64 {
         *len
     }else {
         def_size
     };
-    match value {
-        Value::Number(inner_value, _) => {
-            (inner_value.len() as u64) <= length
-        },
-        Value::SingleQuotedByteStringLiteral(inner_value) => {
-            (inner_value.len() as u64) <= length
-        },
-        Value::DoubleQuotedByteStringLiteral(inner_value) => {
-            (inner_value.len() as u64) <= length
-        },
-        Value::HexStringLiteral(inner_value) => {
-            (inner_value.len() as u64) <= length
-        },
-        Value::UnQuotedString(inner_value) => {
-            (inner_value.len() as u64) <= length
-        },
-        _=>{false}
-    }
+    let bytes = match value {
+        SqlValue::HexStringLiteral(inner_value) => decode_hex(inner_value).ok_or_else(|| "not a valid hex string".to_string())?,
+        SqlValue::Number(inner_value, _) |
+        SqlValue::SingleQuotedByteStringLiteral(inner_value) |
+        SqlValue::DoubleQuotedByteStringLiteral(inner_value) |
+        SqlValue::UnQuotedString(inner_value) => inner_value.as_bytes().to_vec(),
+        _=>{return Err("not a byte string literal".to_string());}
+    };
+    check_bytes_length(bytes, length)
 }
 
 //def precizion 18
-fn validate_value_for_decimal(dec_info:&ExactNumberInfo, value: &Value, def_precizion:u64) -> bool {
+fn validate_value_for_decimal(dec_info:&ExactNumberInfo, value: &SqlValue, def_precizion:u64) -> Result<Value, String> {
     match value {
-        Value::Number(inner_value, _) => {
-            //inner_value.len() <= length
-            if let Err(_) = inner_value.parse::<f64>() {
-                return false;
-            }
-            let parts:Vec<&str> = inner_value.split(".").collect();
-            let value_precizion = (inner_value.len()-1) as u64;
-            if parts.len() != 2 {
-                return false;
-            }
-            let value_scale = parts.get(1).unwrap().len() as u64;
+        SqlValue::Number(inner_value, _) => {
+            let decimal = Decimal::parse(inner_value).ok_or_else(|| "not a decimal number".to_string())?;
+            let value_precizion = inner_value.chars().filter(|c| c.is_ascii_digit()).count() as u64;
+            let value_scale = decimal.scale as u64;
             match dec_info {
-                ExactNumberInfo::None => {
-                    value_precizion <= def_precizion
+                ExactNumberInfo::None if value_precizion > def_precizion => {
+                    Err(format!("more than {} digits", def_precizion))
                 },
-                ExactNumberInfo::Precision(precizion) => {
-                    value_precizion <= *precizion
+                ExactNumberInfo::Precision(precizion) if value_precizion > *precizion => {
+                    Err(format!("more than {} digits", precizion))
                 },
-                ExactNumberInfo::PrecisionAndScale(precizion, scale) => {
-                    (value_precizion <= *precizion) && (value_scale <= *scale)
+                ExactNumberInfo::PrecisionAndScale(precizion, scale) if (value_precizion > *precizion) || (value_scale > *scale) => {
+                    Err(format!("does not fit precision {} and scale {}", precizion, scale))
                 },
+                _ => Ok(Value::Decimal(decimal)),
             }
         },
-        _=>{false}
+        _=>{Err("not a number".to_string())}
     }
 }
 
-fn validate_value_for_float(len_p_u64:&Option<u64>, value: &Value, def_precizion:u64) -> bool {
-    if let Some(len) = len_p_u64 {
-        validate_value_for_decimal(&ExactNumberInfo::None, value, *len)
-    }else {
-        validate_value_for_decimal(&ExactNumberInfo::None, value, def_precizion)
+fn validate_value_for_float(len_p_u64:&Option<u64>, value: &SqlValue, def_precizion:u64) -> Result<Value, String> {
+    let precizion = len_p_u64.unwrap_or(def_precizion);
+    match value {
+        SqlValue::Number(inner_value, _) => {
+            let value_precizion = inner_value.chars().take_while(|c| *c != 'e' && *c != 'E').filter(|c| c.is_ascii_digit()).count() as u64;
+            if value_precizion > precizion {
+                return Err(format!("more than {} digits", precizion));
+            }
+            inner_value.parse::<f64>().map(Value::Float64).map_err(|_| "not a number".to_string())
+        },
+        _=>{Err("not a number".to_string())}
     }
 }
 
-fn validate_value_for_integer(len_p_u64:&Option<u64>, value: &Value, def_precizion:u64, sign: bool) -> bool {
-    let length = if let Some(len) = len_p_u64 {
-        *len
-    }else {
-        def_precizion
-    };
+fn validate_value_for_integer(value: &SqlValue, def_precizion:u64, sign: bool) -> Result<Value, String> {
     match value {
-        Value::Number(inner_value, _) => {
-            let value_len:u64 = inner_value.len() as u64;
-            let can_parse = if sign {
-                inner_value.parse::<i64>().is_ok()
+        SqlValue::Number(inner_value, _) => {
+            let value_len = inner_value.trim_start_matches('-').len() as u64;
+            if value_len > def_precizion {
+                return Err(format!("more than {} digits", def_precizion));
+            }
+            if sign {
+                inner_value.parse::<i64>().map(Value::Int64).map_err(|_| "not an integer".to_string())
             } else {
-                inner_value.parse::<u64>().is_ok()
-            };
-            if !can_parse {
-                return false;
-            }else if value_len > def_precizion {
-                return false;
-            }else {
-                return true;
+                inner_value.parse::<u64>().map(Value::UInt64).map_err(|_| "not an unsigned integer".to_string())
             }
         },
-        _=>{false}
+        _=>{Err("not a number".to_string())}
     }
 }
 
-fn validate_value_for_bool(value: &Value)->bool{
+fn validate_value_for_bool(value: &SqlValue)->Result<Value, String>{
     match value {
-        Value::Number(inner_value, _) => {
-            if inner_value == "1" || inner_value == "0" {
-                return true;
-            }else {
-                return false;
+        SqlValue::Number(inner_value, _) => {
+            match inner_value.as_str() {
+                "1" => Ok(Value::Bool(true)),
+                "0" => Ok(Value::Bool(false)),
+                _ => Err("only 0 and 1 are booleans".to_string())
             }
         }
-        Value::Boolean(_) => {true},
-        _=>{false}
+        SqlValue::Boolean(inner_value) => {Ok(Value::Bool(*inner_value))},
+        _=>{Err("not a boolean".to_string())}
     }
 }
 
@@ -187,63 +161,59 @@ enum DateTypes {
     Timestamp
 }
 
-fn validate_value_for_date_string_general(value_str: &String, date_type: DateTypes) -> bool {
-    match date_type {
-        DateTypes::Date => NaiveDate::parse_from_str(value_str, "%Y-%m-%d").is_ok(),
-        DateTypes::Time => NaiveTime::parse_from_str(value_str, "%H:%M:%S").is_ok(),
-        DateTypes::DateTime => NaiveDateTime::parse_from_str(value_str, "%Y-%m-%d %H:%M:%S").is_ok(),
-        DateTypes::Timestamp => NaiveDateTime::parse_from_str(value_str, "%Y-%m-%d %H:%M:%S.%2f").is_ok(),
-    }
+fn validate_value_for_date_string_general(value_str: &str, date_type: DateTypes) -> Result<Value, String> {
+    let parsed = match date_type {
+        DateTypes::Date => NaiveDate::parse_from_str(value_str, "%Y-%m-%d").map(Value::Date),
+        DateTypes::Time => NaiveTime::parse_from_str(value_str, "%H:%M:%S%.f").map(Value::Time),
+        DateTypes::DateTime => NaiveDateTime::parse_from_str(value_str, "%Y-%m-%d %H:%M:%S").map(Value::Timestamp),
+        DateTypes::Timestamp => NaiveDateTime::parse_from_str(value_str, "%Y-%m-%d %H:%M:%S%.f").map(Value::Timestamp),
+    };
+    parsed.map_err(|e| format!("not a valid {:?}: {}", date_type, e))
 }
 
-fn validate_value_for_dates_general(value: &Value, date_type: DateTypes) -> bool {
+fn validate_value_for_dates_general(value: &SqlValue, date_type: DateTypes) -> Result<Value, String> {
     match value {
-        Value::SingleQuotedString(inner_value) => {
-            validate_value_for_date_string_general(inner_value, date_type)
-        },
-        Value::EscapedStringLiteral(inner_value) => {
+        SqlValue::SingleQuotedString(inner_value) |
+        SqlValue::EscapedStringLiteral(inner_value) |
+        SqlValue::DoubleQuotedString(inner_value) => {
             validate_value_for_date_string_general(inner_value, date_type)
         },
-        Value::DoubleQuotedString(inner_value) => {
-            validate_value_for_date_string_general(inner_value, date_type)
-        },
-        _=>{false}
+        _=>{Err("not a string".to_string())}
     }
 }
 
-fn validate_value_for_json(value: &Value) -> bool {
+fn validate_value_for_json(value: &SqlValue) -> Result<Value, String> {
     match value {
-        Value::SingleQuotedString(inner_value) => {
-            serde_json::from_str::<JsonValue>(inner_value).is_ok()
-        },
-        Value::EscapedStringLiteral(inner_value) => {
-            serde_json::from_str::<JsonValue>(inner_value).is_ok()
-        },
-        Value::DoubleQuotedString(inner_value) => {
-            serde_json::from_str::<JsonValue>(inner_value).is_ok()
-        },
-        _=>{false}
+        SqlValue::SingleQuotedString(inner_value) |
+        SqlValue::EscapedStringLiteral(inner_value) |
+        SqlValue::DoubleQuotedString(inner_value) => {
+            serde_json::from_str::<JsonValue>(inner_value)
+                .map(|json| Value::Json(json.to_string()))
+                .map_err(|e| format!("not valid JSON: {}", e))
+        },
+        _=>{Err("not a string".to_string())}
     }
 }
 
-fn validate_value_for_enum(set:&Vec<String>, value:&Value)-> bool{
+fn validate_value_for_enum(set:&[String], value:&SqlValue)-> Result<Value, String>{
     match value {
-        Value::SingleQuotedString(inner_value) => {
-            set.contains(inner_value)
-        },
-        Value::UnQuotedString(inner_value) => {
-            set.contains(inner_value)
-        },
-        Value::DoubleQuotedString(inner_value) => {
-            set.contains(inner_value)
+        SqlValue::SingleQuotedString(inner_value) |
+        SqlValue::UnQuotedString(inner_value) |
+        SqlValue::DoubleQuotedString(inner_value) => {
+            if set.contains(inner_value) {
+                Ok(Value::Text(inner_value.clone()))
+            } else {
+                Err(format!("not one of {}", set.join(", ")))
+            }
         },
-        _=>{false}
+        _=>{Err("not a string".to_string())}
     }
 }
 
-pub fn validate_value_for_col(type_name: &DataType, value: &Value, nullable:bool) -> bool {
-    if let Value::Null = value {
-        return nullable;
+/// Coerces a literal into the typed value stored for a column of `type_name`, or explains why it does not fit.
+pub fn validate_value_for_col(type_name: &DataType, value: &SqlValue, nullable:bool) -> Result<Value, String> {
+    if let SqlValue::Null = value {
+        return if nullable { Ok(Value::Null) } else { Err("column does not accept NULL".to_string()) };
     }
     match type_name {
         DataType::Character(length_p)=>{
@@ -262,19 +232,19 @@ pub fn validate_value_for_col(type_name: &DataType, value: &Value, nullable:bool
             validate_value_for_char(length_p, value, 30, ABSOLUTE_CHAR_MAX)
         },
         DataType::Nvarchar(len_p_u64)=>{
-            validate_value_for_clob(len_p_u64, value, ABSOLUTE_CHAR_MAX)
+            validate_value_for_clob(len_p_u64, value)
         },
         DataType::Uuid=>{
             validate_value_for_char(&None, value, 30, ABSOLUTE_CHAR_MAX)
         },
         DataType::CharacterLargeObject(len_p_u64)=>{
-            validate_value_for_clob(len_p_u64, value, ABSOLUTE_CHAR_MAX)
+            validate_value_for_clob(len_p_u64, value)
         },
         DataType::CharLargeObject(len_p_u64)=>{
-            validate_value_for_clob(len_p_u64, value, ABSOLUTE_CHAR_MAX)
+            validate_value_for_clob(len_p_u64, value)
         },
         DataType::Clob(len_p_u64)=>{
-            validate_value_for_clob(len_p_u64, value, ABSOLUTE_CHAR_MAX)
+            validate_value_for_clob(len_p_u64, value)
         },
         DataType::Binary(len_p_u64)=>{
             validate_value_for_binary(len_p_u64, value, 30)
@@ -306,60 +276,60 @@ pub fn validate_value_for_col(type_name: &DataType, value: &Value, nullable:bool
         DataType::Float(len_p_u64)=>{
             validate_value_for_float(len_p_u64, value, 18)
         },
-        DataType::TinyInt(len_p_u64)=>{
-            validate_value_for_integer(len_p_u64, value, 4, true)
+        DataType::TinyInt(_len_p_u64)=>{
+            validate_value_for_integer(value, 4, true)
         },
-        DataType::UnsignedTinyInt(len_p_u64)=>{
-            validate_value_for_integer(len_p_u64, value, 3, false)
+        DataType::UnsignedTinyInt(_len_p_u64)=>{
+            validate_value_for_integer(value, 3, false)
         },
-        DataType::Int2(len_p_u64)=>{
-            validate_value_for_integer(len_p_u64, value, 6, false)
+        DataType::Int2(_len_p_u64)=>{
+            validate_value_for_integer(value, 6, true)
         },
-        DataType::UnsignedInt2(len_p_u64)=>{
-            validate_value_for_integer(len_p_u64, value, 5, false)
+        DataType::UnsignedInt2(_len_p_u64)=>{
+            validate_value_for_integer(value, 5, false)
         },
-        DataType::SmallInt(len_p_u64)=>{
-            validate_value_for_integer(len_p_u64, value, 6, false)
+        DataType::SmallInt(_len_p_u64)=>{
+            validate_value_for_integer(value, 6, true)
         },
-        DataType::UnsignedSmallInt(len_p_u64)=>{
-            validate_value_for_integer(len_p_u64, value, 5, false)
+        DataType::UnsignedSmallInt(_len_p_u64)=>{
+            validate_value_for_integer(value, 5, false)
         },
-        DataType::MediumInt(len_p_u64)=>{
-            validate_value_for_integer(len_p_u64, value, 8, false)
+        DataType::MediumInt(_len_p_u64)=>{
+            validate_value_for_integer(value, 8, true)
         },
-        DataType::UnsignedMediumInt(len_p_u64)=>{
-            validate_value_for_integer(len_p_u64, value, 8, false)
+        DataType::UnsignedMediumInt(_len_p_u64)=>{
+            validate_value_for_integer(value, 8, false)
         },
-        DataType::Int(len_p_u64)=>{
-            validate_value_for_integer(len_p_u64, value, 11, false)
+        DataType::Int(_len_p_u64)=>{
+            validate_value_for_integer(value, 11, true)
         },
-        DataType::Int4(len_p_u64)=>{
-            validate_value_for_integer(len_p_u64, value, 11, false)
+        DataType::Int4(_len_p_u64)=>{
+            validate_value_for_integer(value, 11, true)
         },
-        DataType::Int64 => validate_value_for_integer(&None, value, 21, false),
-        DataType::Integer(len_p_u64)=>{
-            validate_value_for_integer(len_p_u64, value, 11, false)
+        DataType::Int64 => validate_value_for_integer(value, 21, true),
+        DataType::Integer(_len_p_u64)=>{
+            validate_value_for_integer(value, 11, true)
         },
-        DataType::UnsignedInt(len_p_u64)=>{
-            validate_value_for_integer(len_p_u64, value, 11, false)
+        DataType::UnsignedInt(_len_p_u64)=>{
+            validate_value_for_integer(value, 11, false)
         },
-        DataType::UnsignedInt4(len_p_u64)=>{
-            validate_value_for_integer(len_p_u64, value, 11, false)
+        DataType::UnsignedInt4(_len_p_u64)=>{
+            validate_value_for_integer(value, 11, false)
         },
-        DataType::UnsignedInteger(len_p_u64)=>{
-            validate_value_for_integer(len_p_u64, value, 11, false)
+        DataType::UnsignedInteger(_len_p_u64)=>{
+            validate_value_for_integer(value, 11, false)
         },
-        DataType::BigInt(len_p_u64)=>{
-            validate_value_for_integer(len_p_u64, value, 21, false)
+        DataType::BigInt(_len_p_u64)=>{
+            validate_value_for_integer(value, 21, true)
         },
-        DataType::UnsignedBigInt(len_p_u64)=>{
-            validate_value_for_integer(len_p_u64, value, 20, false)
+        DataType::UnsignedBigInt(_len_p_u64)=>{
+            validate_value_for_integer(value, 20, false)
         },
-        DataType::Int8(len_p_u64)=>{
-            validate_value_for_integer(len_p_u64, value, 11, false)
+        DataType::Int8(_len_p_u64)=>{
+            validate_value_for_integer(value, 11, true)
         },
-        DataType::UnsignedInt8(len_p_u64)=>{
-            validate_value_for_integer(len_p_u64, value, 11, false)
+        DataType::UnsignedInt8(_len_p_u64)=>{
+            validate_value_for_integer(value, 11, false)
         },
         DataType::Float4=>{
             validate_value_for_float(&None, value, 11)
@@ -386,28 +356,28 @@ pub fn validate_value_for_col(type_name: &DataType, value: &Value, nullable:bool
             validate_value_for_bool(value)
         },
         DataType::Date => {
-            validate_value_for_dates_general(&value, DateTypes::Date)
+            validate_value_for_dates_general(value, DateTypes::Date)
         },
         DataType::Time(_precizion, _timezone_i)=> {
-            validate_value_for_dates_general(&value, DateTypes::Time)
+            validate_value_for_dates_general(value, DateTypes::Time)
         },
         DataType::Datetime(_precizion)=> {
-            validate_value_for_dates_general(&value, DateTypes::DateTime)
+            validate_value_for_dates_general(value, DateTypes::DateTime)
         },
         DataType::Timestamp(_precizion, _timezone_i)=> {
-            validate_value_for_dates_general(&value, DateTypes::Timestamp)
+            validate_value_for_dates_general(value, DateTypes::Timestamp)
         },
         DataType::JSON => {
-            validate_value_for_json(&value)
+            validate_value_for_json(value)
         },
         DataType::JSONB => {
-            validate_value_for_json(&value)
+            validate_value_for_json(value)
         },
         DataType::Text =>{
             validate_value_for_char(&None, value, 60, ABSOLUTE_CHAR_MAX)
         },
         DataType::String(len_p_u64)=>{
-            validate_value_for_clob(len_p_u64, value, ABSOLUTE_CHAR_MAX)
+            validate_value_for_clob(len_p_u64, value)
         },
         DataType::Bytea => {
             validate_value_for_binary(&None, value, 30)
@@ -419,6 +389,124 @@ pub fn validate_value_for_col(type_name: &DataType, value: &Value, nullable:bool
         DataType::Set(set) => {
             validate_value_for_enum(set, value)
         },
-        _=>{false}
+        _=>{Err("unsupported column type".to_string())}
+    }
+}
+/// Number a value stands for when cast to a numeric type: booleans count as 1 and 0, texts are parsed.
+fn number_of(value: &Value) -> Result<Value, String> {
+    match value {
+        value if value.is_numeric() => Ok(value.clone()),
+        Value::Bool(v) => Ok(Value::Int64(*v as i64)),
+        Value::Text(v) => Value::parse_number(v.trim()).ok_or_else(|| "not a number".to_string()),
+        _ => Err("not a number".to_string()),
+    }
+}
+
+/// Rounds a number half away from zero to `digits` decimal places. Floats are rounded as the decimal they print as,
+/// so that 2.345 rounds to 2.35; integers and floats no decimal can hold are left as they are.
+fn round_number(number: Value, digits: i64) -> Result<Value, String> {
+    let decimal = match &number {
+        Value::Decimal(v) => *v,
+        Value::Float64(v) => match Decimal::from_f64(*v) {
+            Some(v) => v,
+            None => { return Ok(number); }
+        },
+        _ => { return Ok(number); }
+    };
+    decimal.round_to(digits, Rounding::HalfAwayFromZero).map(Value::Decimal).ok_or_else(|| "numeric overflow".to_string())
+}
+
+fn truth_of(value: &Value) -> Result<bool, String> {
+    match value {
+        Value::Bool(v) => Ok(*v),
+        value if value.is_numeric() => Ok(value.as_f64() != Some(0.0)),
+        Value::Text(v) => match v.trim().to_lowercase().as_str() {
+            "true" | "t" | "yes" | "y" | "on" | "1" => Ok(true),
+            "false" | "f" | "no" | "n" | "off" | "0" => Ok(false),
+            _ => Err("not a boolean".to_string()),
+        },
+        _ => Err("not a boolean".to_string()),
+    }
+}
+
+/// Converts `value` to a value of `type_name`, as CAST does. Unlike a stored value, a string is cut to the length
+/// of the type, CHAR padding it with spaces, and TEXT or VARCHAR without a length take strings of any length.
+pub fn cast_value(value: &Value, type_name: &DataType) -> Result<Value, String> {
+    let (length, padded) = match type_name {
+        _ if value.is_null() => { return Ok(Value::Null); },
+        DataType::Text | DataType::String(None) | DataType::Nvarchar(None) => (None, false),
+        DataType::String(Some(length)) | DataType::Nvarchar(Some(length)) => (Some(*length), false),
+        DataType::Varchar(length_p) | DataType::CharacterVarying(length_p) | DataType::CharVarying(length_p) => match length_p {
+            Some(CharacterLength::IntegerLength { length, .. }) => (Some(*length), false),
+            Some(CharacterLength::Max) | None => (None, false),
+        },
+        DataType::Char(length_p) | DataType::Character(length_p) => match length_p {
+            Some(CharacterLength::IntegerLength { length, .. }) => (Some(*length), true),
+            Some(CharacterLength::Max) => (None, false),
+            None => (Some(1), true),
+        },
+        _ => { return assign_value(value, type_name); }
+    };
+    let mut text: String = value.to_string();
+    if let Some(length) = length.and_then(|length| usize::try_from(length).ok()) {
+        text = text.chars().take(length).collect();
+        if padded {
+            let missing = length.saturating_sub(text.chars().count());
+            text.extend(std::iter::repeat_n(' ', missing));
+        }
+    }
+    Ok(Value::Text(text))
+}
+
+/// Converts a computed `value` to be stored in a column of `type_name`. Floats are kept as they are and other numbers
+/// are rounded to the scale of the type; the result is then rewritten as the literal the validator of that type reads
+/// and validated like an inserted value.
+pub fn assign_value(value: &Value, type_name: &DataType) -> Result<Value, String> {
+    if value.is_null() {
+        return Ok(Value::Null);
     }
-}
\ No newline at end of file
+    let literal = match type_name {
+        DataType::Float(_) | DataType::Float4 | DataType::Float64 | DataType::Float8 | DataType::Real | DataType::Double | DataType::DoublePrecision => {
+            return Ok(Value::Float64(number_of(value)?.as_f64().unwrap_or_default()));
+        },
+        DataType::TinyInt(_) | DataType::UnsignedTinyInt(_) | DataType::Int2(_) | DataType::UnsignedInt2(_) | DataType::SmallInt(_)
+        | DataType::UnsignedSmallInt(_) | DataType::MediumInt(_) | DataType::UnsignedMediumInt(_) | DataType::Int(_) | DataType::Int4(_)
+        | DataType::Int64 | DataType::Integer(_) | DataType::UnsignedInt(_) | DataType::UnsignedInt4(_) | DataType::UnsignedInteger(_)
+        | DataType::BigInt(_) | DataType::UnsignedBigInt(_) | DataType::Int8(_) | DataType::UnsignedInt8(_) => {
+            SqlValue::Number(round_number(number_of(value)?, 0)?.to_string(), false)
+        },
+        DataType::Numeric(number_info) | DataType::Decimal(number_info) | DataType::BigNumeric(number_info)
+        | DataType::BigDecimal(number_info) | DataType::Dec(number_info) => {
+            let number = match (number_of(value)?, number_info) {
+                (number, ExactNumberInfo::PrecisionAndScale(_, scale)) => round_number(number, *scale as i64)?,
+                (number, _) => number,
+            };
+            SqlValue::Number(number.to_string(), false)
+        },
+        DataType::Bool | DataType::Boolean => SqlValue::Boolean(truth_of(value)?),
+        DataType::Date | DataType::Time(_, _) | DataType::Datetime(_) | DataType::Timestamp(_, _) => {
+            // a text holding a date reads as midnight of that date
+            let value = match value {
+                Value::Text(v) if !matches!(type_name, DataType::Date | DataType::Time(_, _)) => match NaiveDate::parse_from_str(v.trim(), "%Y-%m-%d") {
+                    Ok(date) => Value::Date(date),
+                    Err(_) => Value::Text(v.trim().to_string()),
+                },
+                value => value.clone(),
+            };
+            let text = match (&value, type_name) {
+                (Value::Timestamp(v), DataType::Date) => v.date().format("%Y-%m-%d").to_string(),
+                (Value::Timestamp(v), DataType::Time(_, _)) => v.time().format("%H:%M:%S%.f").to_string(),
+                (Value::Timestamp(v), DataType::Datetime(_)) => v.format("%Y-%m-%d %H:%M:%S").to_string(),
+                (Value::Date(v), DataType::Datetime(_) | DataType::Timestamp(_, _)) => v.and_time(NaiveTime::MIN).format("%Y-%m-%d %H:%M:%S").to_string(),
+                (value, _) => value.to_string(),
+            };
+            SqlValue::SingleQuotedString(text)
+        },
+        DataType::Binary(_) | DataType::Varbinary(_) | DataType::Blob(_) | DataType::Bytes(_) | DataType::Bytea => match value {
+            Value::Bytes(v) => SqlValue::HexStringLiteral(v.iter().map(|b| format!("{:02X}", b)).collect()),
+            value => SqlValue::SingleQuotedByteStringLiteral(value.to_string()),
+        },
+        _ => SqlValue::SingleQuotedString(value.to_string()),
+    };
+    validate_value_for_col(type_name, &literal, true)
+}
@@ -0,0 +1,396 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use serde::{Deserialize, Serialize};
+use sqlparser::ast::Value as SqlValue;
+
+/// Fixed point number stored as `mantissa * 10^-scale`.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Decimal {
+    pub mantissa: i64,
+    pub scale: u32,
+}
+
+/// A typed cell value, produced by the validators at insert time.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Value {
+    Null,
+    Int64(i64),
+    UInt64(u64),
+    Float64(f64),
+    Decimal(Decimal),
+    Bool(bool),
+    Text(String),
+    Bytes(Vec<u8>),
+    Date(NaiveDate),
+    Time(NaiveTime),
+    Timestamp(NaiveDateTime),
+    Json(String),
+}
+
+impl Decimal {
+    pub fn parse(value_str: &str) -> Option<Self> {
+        let (sign, digits) = match value_str.strip_prefix('-') {
+            Some(rest) => (-1, rest),
+            None => (1, value_str.strip_prefix('+').unwrap_or(value_str)),
+        };
+        let (int_part, frac_part) = match digits.split_once('.') {
+            Some((int_part, frac_part)) => (int_part, frac_part),
+            None => (digits, ""),
+        };
+        if int_part.is_empty() && frac_part.is_empty() {
+            return None;
+        }
+        if !int_part.chars().chain(frac_part.chars()).all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        // the sign is applied before narrowing, so that i64::MIN still fits
+        let mut mantissa: i128 = 0;
+        for c in int_part.chars().chain(frac_part.chars()) {
+            mantissa = mantissa.checked_mul(10)?.checked_add((c as u8 - b'0') as i128)?;
+        }
+        Some(Self {
+            mantissa: i64::try_from(mantissa * sign).ok()?,
+            scale: frac_part.len() as u32,
+        })
+    }
+
+    /// The decimal `v` prints as, so that 2.675 stays 2.675 rather than the binary fraction just below it.
+    /// `None` for floats too large or too precise to fit, and for infinities and NaN.
+    pub fn from_f64(v: f64) -> Option<Self> {
+        Self::parse(&v.to_string())
+    }
+
+    /// Converts from the normalized form, so that equal decimals always give the same float.
+    pub fn to_f64(self) -> f64 {
+        let v = self.normalize();
+        v.mantissa as f64 / 10f64.powi(v.scale as i32)
+    }
+
+    /// Rescales to `scale` digits, `None` on overflow.
+    pub fn rescale(self, scale: u32) -> Option<Self> {
+        if scale >= self.scale {
+            let factor = 10i64.checked_pow(scale - self.scale)?;
+            Some(Self { mantissa: self.mantissa.checked_mul(factor)?, scale })
+        } else {
+            let factor = 10i64.checked_pow(self.scale - scale)?;
+            Some(Self { mantissa: self.mantissa / factor, scale })
+        }
+    }
+
+    /// Drops trailing zeros so that equal numbers share one representation.
+    pub fn normalize(self) -> Self {
+        let mut ret = self;
+        while ret.scale > 0 && ret.mantissa % 10 == 0 {
+            ret.mantissa /= 10;
+            ret.scale -= 1;
+        }
+        ret
+    }
+
+    pub fn checked_add(self, other: Self) -> Option<Self> {
+        let scale = self.scale.max(other.scale);
+        let (l, r) = (self.rescale(scale)?, other.rescale(scale)?);
+        Some(Self { mantissa: l.mantissa.checked_add(r.mantissa)?, scale })
+    }
+
+    pub fn checked_sub(self, other: Self) -> Option<Self> {
+        self.checked_add(Self { mantissa: other.mantissa.checked_neg()?, scale: other.scale })
+    }
+
+    pub fn checked_mul(self, other: Self) -> Option<Self> {
+        Some(Self {
+            mantissa: self.mantissa.checked_mul(other.mantissa)?,
+            scale: self.scale + other.scale,
+        })
+    }
+
+    /// Rounds to `digits` decimal places the `rounding` way, negative digits rounding to tens, hundreds and so on.
+    /// Digits the decimal does not have are not added; `None` when the result does not fit.
+    pub fn round_to(self, digits: i64, rounding: Rounding) -> Option<Self> {
+        if digits >= self.scale as i64 {
+            return Some(self);
+        }
+        let factor = 10i128.checked_pow((self.scale as i64 - digits) as u32);
+        let rounded = factor.map_or(0, |factor| rounding.divide(self.mantissa as i128, factor));
+        if digits >= 0 {
+            // never wider than the mantissa it comes from
+            Some(Self { mantissa: rounded as i64, scale: digits as u32 })
+        } else {
+            let mantissa = rounded.checked_mul(10i128.checked_pow(-digits as u32)?)?;
+            Some(Self { mantissa: i64::try_from(mantissa).ok()?, scale: 0 })
+        }
+    }
+}
+
+/// Way the digits dropped by rounding are accounted for.
+#[derive(Debug, Clone, Copy)]
+pub enum Rounding {
+    HalfAwayFromZero,
+    Down,
+    Up,
+}
+
+impl Rounding {
+    /// `value / factor` rounded this way, `factor` being positive.
+    pub fn divide(self, value: i128, factor: i128) -> i128 {
+        let (quotient, remainder) = (value / factor, value % factor);
+        match self {
+            Rounding::HalfAwayFromZero if remainder.abs() * 2 >= factor => quotient + value.signum(),
+            Rounding::Down if remainder < 0 => quotient - 1,
+            Rounding::Up if remainder > 0 => quotient + 1,
+            _ => quotient,
+        }
+    }
+}
+
+impl PartialEq for Decimal {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Decimal {}
+
+impl PartialOrd for Decimal {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Decimal {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let scale = self.scale.max(other.scale);
+        match (self.rescale(scale), other.rescale(scale)) {
+            (Some(l), Some(r)) => l.mantissa.cmp(&r.mantissa),
+            _ => self.to_f64().total_cmp(&other.to_f64()),
+        }
+    }
+}
+
+impl fmt::Display for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.scale == 0 {
+            return write!(f, "{}", self.mantissa);
+        }
+        let sign = if self.mantissa < 0 { "-" } else { "" };
+        let digits = format!("{:0>width$}", self.mantissa.unsigned_abs(), width = self.scale as usize + 1);
+        let (int_part, frac_part) = digits.split_at(digits.len() - self.scale as usize);
+        write!(f, "{}{}.{}", sign, int_part, frac_part)
+    }
+}
+
+/// Groups values that can be compared with each other, also used to order values of different families.
+fn type_rank(value: &Value) -> u8 {
+    match value {
+        Value::Null => 0,
+        Value::Bool(_) => 1,
+        Value::Int64(_) | Value::UInt64(_) | Value::Float64(_) | Value::Decimal(_) => 2,
+        Value::Text(_) => 3,
+        Value::Bytes(_) => 4,
+        Value::Date(_) => 5,
+        Value::Time(_) => 6,
+        Value::Timestamp(_) => 7,
+        Value::Json(_) => 8,
+    }
+}
+
+impl Value {
+    pub fn from_sql_literal(value: &SqlValue) -> Self {
+        match value {
+            SqlValue::Number(v, _l) => Self::parse_number(v).unwrap_or_else(|| Value::Text(v.clone())),
+            SqlValue::SingleQuotedString(v)
+            | SqlValue::DoubleQuotedString(v)
+            | SqlValue::EscapedStringLiteral(v)
+            | SqlValue::NationalStringLiteral(v)
+            | SqlValue::RawStringLiteral(v)
+            | SqlValue::UnQuotedString(v) => Value::Text(v.clone()),
+            SqlValue::DollarQuotedString(v) => Value::Text(v.value.clone()),
+            SqlValue::SingleQuotedByteStringLiteral(v)
+            | SqlValue::DoubleQuotedByteStringLiteral(v) => Value::Bytes(v.as_bytes().to_vec()),
+            SqlValue::HexStringLiteral(v) => decode_hex(v).map(Value::Bytes).unwrap_or(Value::Null),
+            SqlValue::Boolean(v) => Value::Bool(*v),
+            _ => Value::Null,
+        }
+    }
+
+    /// Parses a numeric literal into the narrowest exact representation.
+    pub fn parse_number(value_str: &str) -> Option<Self> {
+        if let Ok(v) = value_str.parse::<i64>() {
+            Some(Value::Int64(v))
+        } else if let Ok(v) = value_str.parse::<u64>() {
+            Some(Value::UInt64(v))
+        } else if value_str.contains(['e', 'E']) {
+            value_str.parse::<f64>().ok().map(Value::Float64)
+        } else if let Some(v) = Decimal::parse(value_str) {
+            Some(Value::Decimal(v))
+        } else {
+            value_str.parse::<f64>().ok().map(Value::Float64)
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        matches!(self, Value::Null)
+    }
+
+    /// Whether values of both types can share a column, as numbers of different kinds or dates and timestamps do.
+    pub fn is_compatible_with(&self, other: &Value) -> bool {
+        let ranks = (type_rank(self), type_rank(other));
+        ranks.0 == ranks.1 || matches!(ranks, (5, 7) | (7, 5))
+    }
+
+    /// Whether both values belong to the same type family, the only case where `compare` finding them equal
+    /// agrees with `==` and their hashes.
+    pub fn is_same_family(&self, other: &Value) -> bool {
+        type_rank(self) == type_rank(other)
+    }
+
+    pub fn is_numeric(&self) -> bool {
+        type_rank(self) == 2
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Int64(v) => Some(*v as f64),
+            Value::UInt64(v) => Some(*v as f64),
+            Value::Float64(v) => Some(*v),
+            Value::Decimal(v) => Some(v.to_f64()),
+            _ => None,
+        }
+    }
+
+    fn as_i128(&self) -> Option<i128> {
+        match self {
+            Value::Int64(v) => Some(*v as i128),
+            Value::UInt64(v) => Some(*v as i128),
+            _ => None,
+        }
+    }
+
+    /// SQL comparison: `None` when either side is NULL or the types are not comparable.
+    pub fn compare(&self, other: &Value) -> Option<Ordering> {
+        match (self, other) {
+            (Value::Null, _) | (_, Value::Null) => None,
+            (Value::Bool(l), Value::Bool(r)) => Some(l.cmp(r)),
+            (Value::Text(l), Value::Text(r)) => Some(l.cmp(r)),
+            (Value::Bytes(l), Value::Bytes(r)) => Some(l.cmp(r)),
+            (Value::Json(l), Value::Json(r)) => Some(l.cmp(r)),
+            (Value::Date(l), Value::Date(r)) => Some(l.cmp(r)),
+            (Value::Time(l), Value::Time(r)) => Some(l.cmp(r)),
+            (Value::Timestamp(l), Value::Timestamp(r)) => Some(l.cmp(r)),
+            (Value::Date(l), Value::Timestamp(r)) => Some(l.and_time(NaiveTime::MIN).cmp(r)),
+            (Value::Timestamp(l), Value::Date(r)) => Some(l.cmp(&r.and_time(NaiveTime::MIN))),
+            (Value::Decimal(l), Value::Decimal(r)) => Some(l.cmp(r)),
+            (Value::Date(_) | Value::Time(_) | Value::Timestamp(_), Value::Text(r)) => {
+                self.compare(&Value::parse_temporal_like(self, r)?)
+            },
+            (Value::Text(l), Value::Date(_) | Value::Time(_) | Value::Timestamp(_)) => {
+                Value::parse_temporal_like(other, l)?.compare(other)
+            },
+            (l, r) if l.is_numeric() && r.is_numeric() => {
+                if let (Some(l_int), Some(r_int)) = (l.as_i128(), r.as_i128()) {
+                    Some(l_int.cmp(&r_int))
+                } else {
+                    l.as_f64()?.partial_cmp(&r.as_f64()?)
+                }
+            },
+            _ => None,
+        }
+    }
+
+    /// Parses `text` as the same temporal type as `like`.
+    fn parse_temporal_like(like: &Value, text: &str) -> Option<Value> {
+        match like {
+            Value::Date(_) => NaiveDate::parse_from_str(text, "%Y-%m-%d").ok().map(Value::Date),
+            Value::Time(_) => NaiveTime::parse_from_str(text, "%H:%M:%S%.f").ok().map(Value::Time),
+            Value::Timestamp(_) => NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S%.f")
+                .ok()
+                .or_else(|| NaiveDate::parse_from_str(text, "%Y-%m-%d").ok().map(|d| d.and_time(NaiveTime::MIN)))
+                .map(Value::Timestamp),
+            _ => None,
+        }
+    }
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Value {}
+
+impl PartialOrd for Value {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Total order used for sorting and keys: NULL sorts first, then values grouped by type family.
+impl Ord for Value {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Value::Null, Value::Null) => Ordering::Equal,
+            (Value::Float64(l), Value::Float64(r)) => l.total_cmp(r),
+            _ => {
+                let (l_rank, r_rank) = (type_rank(self), type_rank(other));
+                if l_rank != r_rank {
+                    return l_rank.cmp(&r_rank);
+                }
+                self.compare(other).unwrap_or_else(|| {
+                    self.as_f64().unwrap_or(f64::NAN).total_cmp(&other.as_f64().unwrap_or(f64::NAN))
+                })
+            }
+        }
+    }
+}
+
+impl Hash for Value {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        type_rank(self).hash(state);
+        match self {
+            Value::Null => {},
+            Value::Bool(v) => v.hash(state),
+            Value::Text(v) | Value::Json(v) => v.hash(state),
+            Value::Bytes(v) => v.hash(state),
+            Value::Date(v) => v.hash(state),
+            Value::Time(v) => v.hash(state),
+            Value::Timestamp(v) => v.hash(state),
+            // numbers of different kinds compare as floats, so they hash as the float they convert to
+            Value::Int64(_) | Value::UInt64(_) | Value::Float64(_) | Value::Decimal(_) => {
+                // 0.0 and -0.0 both equal the integer 0
+                let v = self.as_f64().filter(|v| *v != 0.0).unwrap_or(0.0);
+                v.to_bits().hash(state)
+            },
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Null => write!(f, "NULL"),
+            Value::Int64(v) => write!(f, "{}", v),
+            Value::UInt64(v) => write!(f, "{}", v),
+            Value::Float64(v) => write!(f, "{}", v),
+            Value::Decimal(v) => write!(f, "{}", v),
+            Value::Bool(v) => write!(f, "{}", v),
+            Value::Text(v) | Value::Json(v) => write!(f, "{}", v),
+            Value::Bytes(v) => write!(f, "{}", String::from_utf8_lossy(v)),
+            Value::Date(v) => write!(f, "{}", v.format("%Y-%m-%d")),
+            Value::Time(v) => write!(f, "{}", v.format("%H:%M:%S%.f")),
+            Value::Timestamp(v) => write!(f, "{}", v.format("%Y-%m-%d %H:%M:%S%.f")),
+        }
+    }
+}
+
+pub fn decode_hex(hex_str: &str) -> Option<Vec<u8>> {
+    if !hex_str.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex_str.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex_str.get(i..i + 2)?, 16).ok())
+        .collect()
+}
@@ -0,0 +1,348 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::ops::ControlFlow;
+use std::rc::Rc;
+use sqlparser::ast::{BinaryOperator, Expr, Function, FunctionArg, FunctionArgExpr, Query, Select, Visit, Visitor, WindowFrameBound, WindowFrameUnits,
+    WindowSpec, WindowType};
+use crate::aggregate::{Accumulator, is_aggregate_name};
+use crate::error::DbError;
+use crate::executor::ResultSet;
+use crate::expression::{ColumnLookup, EmptyRow, eval_binary_op, eval_expr};
+use crate::query::{SortKey, compare_sort_values};
+use crate::value::Value;
+
+/// Whether `function` is computed over a window of rows related to the current one (it has OVER).
+pub fn is_window(function: &Function) -> bool {
+    function.over.is_some()
+}
+
+/// Collects the window calls of expressions, leaving out those of nested subqueries.
+struct WindowCollector {
+    query_depth: usize,
+    found: Vec<Expr>,
+}
+
+impl Visitor for WindowCollector {
+    type Break = ();
+
+    fn pre_visit_query(&mut self, _query: &Query) -> ControlFlow<Self::Break> {
+        self.query_depth += 1;
+        ControlFlow::Continue(())
+    }
+
+    fn post_visit_query(&mut self, _query: &Query) -> ControlFlow<Self::Break> {
+        self.query_depth -= 1;
+        ControlFlow::Continue(())
+    }
+
+    fn pre_visit_expr(&mut self, expr: &Expr) -> ControlFlow<Self::Break> {
+        if let Expr::Function(function) = expr {
+            if self.query_depth == 0 && is_window(function) && !self.found.contains(expr) {
+                self.found.push(expr.clone());
+            }
+        }
+        ControlFlow::Continue(())
+    }
+}
+
+/// Number of rows given to a ROWS frame bound, which must be a non-negative integer.
+fn rows_offset(expr: &Expr) -> Result<i64, DbError> {
+    match eval_expr(expr, &EmptyRow)? {
+        Value::Int64(offset) if offset >= 0 => Ok(offset),
+        Value::UInt64(offset) => Ok(offset.min(i64::MAX as u64) as i64),
+        value => Err(DbError::Execution(format!("ROWS frame offset must be a non-negative integer, got {}", value))),
+    }
+}
+
+/// Rows of one partition, in window order.
+struct Partition {
+    order_values: Vec<Vec<Value>>,//values of the window ORDER BY
+    positions: Vec<usize>,//positions of the rows among those given to the window
+    peers: Vec<(usize, usize)>,//start and end of the rows each row ties with on ORDER BY
+}
+
+impl Partition {
+    fn new(rows: Vec<(Vec<Value>, usize)>, sort_keys: &[SortKey]) -> Self {
+        let (order_values, positions): (Vec<_>, Vec<_>) = rows.into_iter().unzip();
+        let mut peers = Vec::with_capacity(order_values.len());
+        let mut start = 0;
+        for index in 1..=order_values.len() {
+            if index == order_values.len() || compare_sort_values(&order_values[start], &order_values[index], sort_keys) != Ordering::Equal {
+                peers.extend(std::iter::repeat_n((start, index), index - start));
+                start = index;
+            }
+        }
+        Self { order_values, positions, peers }
+    }
+
+    fn len(&self) -> usize {
+        self.positions.len()
+    }
+}
+
+/// A window call along with the window it is computed over.
+struct WindowCall {
+    expr: Expr,
+    function: Function,
+    spec: WindowSpec,
+}
+
+impl WindowCall {
+    /// Value of the call for each of `rows`.
+    fn evaluate<R: ColumnLookup>(&self, rows: &[R]) -> Result<Vec<Value>, DbError> {
+        // partitions keep the order in which their first row comes
+        let mut partition_positions: HashMap<Vec<Value>, usize> = HashMap::new();
+        let mut partitions: Vec<Vec<(Vec<Value>, usize)>> = Vec::new();//ORDER BY values and position of each row
+        for (position, row) in rows.iter().enumerate() {
+            let partition_key = self.spec.partition_by.iter().map(|expr| eval_expr(expr, row)).collect::<Result<Vec<_>, _>>()?;
+            let order_values = self.spec.order_by.iter().map(|order_by| eval_expr(&order_by.expr, row)).collect::<Result<Vec<_>, _>>()?;
+            let partition = *partition_positions.entry(partition_key).or_insert_with(|| {
+                partitions.push(Vec::new());
+                partitions.len() - 1
+            });
+            partitions[partition].push((order_values, position));
+        }
+
+        let sort_keys: Vec<SortKey> = self.spec.order_by.iter().map(SortKey::of_window).collect();
+        let mut ret = vec![Value::Null; rows.len()];
+        for mut partition_rows in partitions {
+            partition_rows.sort_by(|(l_values, _), (r_values, _)| compare_sort_values(l_values, r_values, &sort_keys));
+            let partition = Partition::new(partition_rows, &sort_keys);
+            for (index, value) in self.evaluate_partition(&partition, rows)?.into_iter().enumerate() {
+                ret[partition.positions[index]] = value;
+            }
+        }
+        Ok(ret)
+    }
+
+    /// Value of the call for each row of `partition`, in window order.
+    fn evaluate_partition<R: ColumnLookup>(&self, partition: &Partition, rows: &[R]) -> Result<Vec<Value>, DbError> {
+        let function_name = self.function.name.to_string().to_uppercase();
+        if is_aggregate_name(&function_name) {
+            return self.aggregate(partition, rows);
+        }
+        let args = self.function.args.iter().map(|arg| match arg {
+            FunctionArg::Unnamed(FunctionArgExpr::Expr(expr)) => Ok(expr),
+            arg => Err(DbError::Unsupported(format!("argument {} for {}", arg, self.function.name))),
+        }).collect::<Result<Vec<_>, _>>()?;
+        let expect_args = |counts: &[usize]| -> Result<(), DbError> {
+            if !counts.contains(&args.len()) {
+                return Err(DbError::Execution(format!("{} expects {} argument(s), got {}", function_name, counts[0], args.len())));
+            }
+            Ok(())
+        };
+        let row_at = |index: usize| &rows[partition.positions[index]];
+        let count = partition.len();
+        match function_name.as_str() {
+            "ROW_NUMBER" => {
+                expect_args(&[0])?;
+                Ok((1..=count).map(|number| Value::Int64(number as i64)).collect())
+            },
+            // ties share the rank of their first row, RANK then skipping as many ranks as there were ties
+            "RANK" => {
+                expect_args(&[0])?;
+                Ok(partition.peers.iter().map(|(start, _)| Value::Int64(*start as i64 + 1)).collect())
+            },
+            "DENSE_RANK" => {
+                expect_args(&[0])?;
+                let mut rank = 0;
+                Ok(partition.peers.iter().enumerate().map(|(index, (start, _))| {
+                    if *start == index {
+                        rank += 1;
+                    }
+                    Value::Int64(rank)
+                }).collect())
+            },
+            "LAG" | "LEAD" => {
+                expect_args(&[1, 2, 3])?;
+                let offset = args.get(1).map(|offset| rows_offset(offset)).transpose()?.unwrap_or(1);
+                let offset = usize::try_from(offset).unwrap_or(usize::MAX);
+                (0..count).map(|index| {
+                    let target = match function_name.as_str() {
+                        "LAG" => index.checked_sub(offset),
+                        _ => index.checked_add(offset).filter(|target| *target < count),
+                    };
+                    match (target, args.get(2)) {
+                        (Some(target), _) => eval_expr(args[0], row_at(target)),
+                        (None, Some(default)) => eval_expr(default, row_at(index)),
+                        (None, None) => Ok(Value::Null),
+                    }
+                }).collect()
+            },
+            "FIRST_VALUE" | "LAST_VALUE" => {
+                expect_args(&[1])?;
+                (0..count).map(|index| {
+                    let (start, end) = self.frame(partition, index)?;
+                    match function_name.as_str() {
+                        _ if start == end => Ok(Value::Null),
+                        "FIRST_VALUE" => eval_expr(args[0], row_at(start)),
+                        _ => eval_expr(args[0], row_at(end - 1)),
+                    }
+                }).collect()
+            },
+            _ => Err(DbError::Unsupported(format!("window function {}", self.function.name))),
+        }
+    }
+
+    /// Value of an aggregate call over the frame of each row of `partition`.
+    fn aggregate<R: ColumnLookup>(&self, partition: &Partition, rows: &[R]) -> Result<Vec<Value>, DbError> {
+        let row_at = |index: usize| &rows[partition.positions[index]];
+        let mut ret = Vec::with_capacity(partition.len());
+        // frames starting at the first row only grow, so one accumulator follows them all
+        let mut running: Option<(Accumulator, usize)> = None;//accumulator, rows added to it
+        for index in 0..partition.len() {
+            let (start, end) = self.frame(partition, index)?;
+            if start == 0 {
+                let (accumulator, added) = match &mut running {
+                    Some(running) => running,
+                    None => running.insert((Accumulator::new(&self.function)?, 0)),
+                };
+                while *added < end {
+                    accumulator.update(row_at(*added))?;
+                    *added += 1;
+                }
+                ret.push(accumulator.finish());
+            } else {
+                let mut accumulator = Accumulator::new(&self.function)?;
+                for frame_index in start..end {
+                    accumulator.update(row_at(frame_index))?;
+                }
+                ret.push(accumulator.finish());
+            }
+        }
+        Ok(ret)
+    }
+
+    /// Start and end (exclusive) in `partition` of the frame of the row at `index`.
+    /// Without a frame clause it runs from the first row to the last peer of the current one, which is the whole
+    /// partition when the window has no ORDER BY.
+    fn frame(&self, partition: &Partition, index: usize) -> Result<(usize, usize), DbError> {
+        let Some(frame) = &self.spec.window_frame else {
+            return Ok((0, partition.peers[index].1));
+        };
+        let start = self.frame_bound(frame.units, &frame.start_bound, partition, index, true)?;
+        let end = self.frame_bound(frame.units, frame.end_bound.as_ref().unwrap_or(&WindowFrameBound::CurrentRow), partition, index, false)?;
+        Ok((start, end.max(start)))
+    }
+
+    /// Position in `partition` where the frame of the row at `index` starts, or where it ends when `is_start` is false.
+    fn frame_bound(&self, units: WindowFrameUnits, bound: &WindowFrameBound, partition: &Partition, index: usize, is_start: bool) -> Result<usize, DbError> {
+        match (units, bound) {
+            (WindowFrameUnits::Groups, _) => Err(DbError::Unsupported("GROUPS window frames".to_string())),
+            (_, WindowFrameBound::Preceding(None)) => Ok(0),
+            (_, WindowFrameBound::Following(None)) => Ok(partition.len()),
+            (WindowFrameUnits::Rows, bound) => {
+                let position = match bound {
+                    WindowFrameBound::Preceding(Some(offset)) => (index as i64).saturating_sub(rows_offset(offset)?),
+                    WindowFrameBound::Following(Some(offset)) => (index as i64).saturating_add(rows_offset(offset)?),
+                    _ => index as i64,
+                };
+                let position = if is_start { position } else { position.saturating_add(1) };
+                Ok(position.clamp(0, partition.len() as i64) as usize)
+            },
+            (WindowFrameUnits::Range, WindowFrameBound::CurrentRow) => {
+                let (start, end) = partition.peers[index];
+                Ok(if is_start { start } else { end })
+            },
+            (WindowFrameUnits::Range, WindowFrameBound::Preceding(Some(offset)) | WindowFrameBound::Following(Some(offset))) => {
+                let [order_by] = self.spec.order_by.as_slice() else {
+                    return Err(DbError::Execution("RANGE with an offset requires exactly one ORDER BY key".to_string()));
+                };
+                let offset = eval_expr(offset, &EmptyRow)?;
+                if !offset.is_numeric() || offset.compare(&Value::Int64(0)) == Some(Ordering::Less) {
+                    return Err(DbError::Execution(format!("RANGE frame offset must be a non-negative number, got {}", offset)));
+                }
+                let current = &partition.order_values[index][0];
+                if current.is_null() {
+                    // a NULL is no distance from anything but the other NULLs
+                    let (start, end) = partition.peers[index];
+                    return Ok(if is_start { start } else { end });
+                }
+                if !current.is_numeric() {
+                    return Err(DbError::Execution(format!("RANGE with an offset requires a numeric ORDER BY key, got {}", current)));
+                }
+                let SortKey { descending, nulls_first, .. } = SortKey::of_window(order_by);
+                let toward_end = matches!(bound, WindowFrameBound::Following(_));
+                let op = if toward_end != descending { BinaryOperator::Plus } else { BinaryOperator::Minus };
+                let target = eval_binary_op(current, &op, &offset)?;
+                // rows are in window order, so those before the bound value form a prefix
+                Ok(partition.order_values.partition_point(|values| {
+                    let ordering = match &values[0] {
+                        value if value.is_null() => if nulls_first { Ordering::Less } else { Ordering::Greater },
+                        value if descending => value.compare(&target).unwrap_or(Ordering::Equal).reverse(),
+                        value => value.compare(&target).unwrap_or(Ordering::Equal),
+                    };
+                    ordering == Ordering::Less || (ordering == Ordering::Equal && !is_start)
+                }))
+            },
+        }
+    }
+}
+
+/// Window calls of a SELECT, computed over its rows once they are filtered and grouped.
+pub struct Windows {
+    calls: Vec<WindowCall>,
+}
+
+impl Windows {
+    /// None when `exprs` call no window function. Windows named in OVER are looked up in the WINDOW clause of `select_q`.
+    pub fn new<'a>(select_q: &Select, exprs: impl IntoIterator<Item = &'a Expr>) -> Result<Option<Self>, DbError> {
+        let mut collector = WindowCollector { query_depth: 0, found: Vec::new() };
+        for expr in exprs {
+            let _ = expr.visit(&mut collector);
+        }
+        if collector.found.is_empty() {
+            return Ok(None);
+        }
+        let mut calls = Vec::with_capacity(collector.found.len());
+        for expr in collector.found {
+            let Expr::Function(function) = &expr else {
+                continue;
+            };
+            let spec = match &function.over {
+                Some(WindowType::WindowSpec(spec)) => spec.clone(),
+                Some(WindowType::NamedWindow(name)) => select_q.named_window.iter().find(|definition| definition.0.value == name.value)
+                    .map(|definition| definition.1.clone()).ok_or_else(|| DbError::Parse(format!("window {} is not defined", name)))?,
+                None => continue,
+            };
+            let function = function.clone();
+            calls.push(WindowCall { expr, function, spec });
+        }
+        Ok(Some(Self { calls }))
+    }
+
+    /// Values of every window call for each of `rows`, in the order of `rows`.
+    pub fn evaluate<R: ColumnLookup>(&self, rows: &[R]) -> Result<Vec<Vec<Value>>, DbError> {
+        let mut values = vec![Vec::with_capacity(self.calls.len()); rows.len()];
+        for call in &self.calls {
+            for (position, value) in call.evaluate(rows)?.into_iter().enumerate() {
+                values[position].push(value);
+            }
+        }
+        Ok(values)
+    }
+}
+
+/// A row seen along with the values the window calls of its SELECT take on it.
+pub struct WindowScope<'a> {
+    pub row: &'a dyn ColumnLookup,
+    pub windows: &'a Windows,
+    pub values: Vec<Value>,
+}
+
+impl ColumnLookup for WindowScope<'_> {
+    fn lookup(&self, qualifier: Option<&str>, name: &str) -> Result<Value, DbError> {
+        self.row.lookup(qualifier, name)
+    }
+
+    fn precomputed(&self, expr: &Expr) -> Option<Value> {
+        match self.windows.calls.iter().position(|call| call.expr == *expr) {
+            Some(position) => Some(self.values[position].clone()),
+            None => self.row.precomputed(expr),
+        }
+    }
+
+    fn run_subquery(&self, query: &Query, row: &dyn ColumnLookup) -> Result<Rc<ResultSet>, DbError> {
+        self.row.run_subquery(query, row)
+    }
+}
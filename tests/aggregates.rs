@@ -0,0 +1,90 @@
+mod common;
+
+use common::{column, fetch, run, text};
+use in_memory_db::data_descriptor::DataBase;
+use in_memory_db::error::DbError;
+use in_memory_db::value::{Decimal, Value};
+
+fn sales() -> DataBase {
+    let mut db = DataBase::new();
+    run(&mut db, "CREATE TABLE sales (id INT PRIMARY KEY, region VARCHAR(10), product VARCHAR(10), amount INT, price DECIMAL(6,2))").unwrap();
+    run(&mut db, "INSERT INTO sales (id, region, product, amount, price) VALUES (1, 'north', 'tea', 10, 2.50), (2, 'north', 'coffee', 5, 4.00), \
+        (3, 'south', 'tea', 7, 2.50), (4, 'north', 'tea', 3, 2.75), (5, 'east', 'cake', NULL, NULL)").unwrap();
+    db
+}
+
+#[test]
+fn aggregates_without_group_by_summarize_every_row() {
+    let mut db = sales();
+    let totals = fetch(&mut db, "SELECT COUNT(*), COUNT(amount), SUM(amount), MIN(price), MAX(product), AVG(amount), COUNT(DISTINCT product) FROM sales");
+    assert_eq!(totals.rows, vec![vec![Value::Int64(5), Value::Int64(4), Value::Int64(25), Value::Decimal(Decimal { mantissa: 250, scale: 2 }),
+        text("tea"), Value::Float64(6.25), Value::Int64(3)]]);
+}
+
+#[test]
+fn group_by_with_having_and_order_by_an_aggregate() {
+    let mut db = sales();
+    let by_region = fetch(&mut db, "SELECT region, COUNT(*) AS orders, SUM(amount) + 1 FROM sales GROUP BY region HAVING COUNT(*) > 1 OR region = 'east' ORDER BY orders DESC");
+    assert_eq!(by_region.columns, vec!["region", "orders", "SUM(amount) + 1"]);
+    assert_eq!(by_region.rows, vec![
+        vec![text("north"), Value::Int64(3), Value::Int64(19)],
+        vec![text("east"), Value::Int64(1), Value::Null],
+    ]);
+}
+
+#[test]
+fn string_aggregates_follow_their_own_order() {
+    let mut db = sales();
+    let products = fetch(&mut db, "SELECT region, string_agg(product, '/' ORDER BY id DESC), group_concat(DISTINCT product ORDER BY product DESC) \
+        FROM sales WHERE region = 'north' GROUP BY 1");
+    assert_eq!(products.rows, vec![vec![text("north"), text("tea/coffee/tea"), text("tea,coffee")]]);
+}
+
+#[test]
+fn group_by_aliases_and_several_keys() {
+    let mut db = sales();
+    let pairs = fetch(&mut db, "SELECT region AS r, product, SUM(amount) FROM sales WHERE amount > 4 OR amount IS NULL GROUP BY r, product ORDER BY r, product");
+    assert_eq!(pairs.rows.len(), 4);
+    assert_eq!(pairs.rows[2], vec![text("north"), text("tea"), Value::Int64(10)]);
+    assert_eq!(pairs.rows[0][2], Value::Null);
+}
+
+#[test]
+fn aggregates_over_no_rows() {
+    let mut db = sales();
+    assert_eq!(fetch(&mut db, "SELECT COUNT(*), SUM(amount) FROM sales WHERE id > 10").rows, vec![vec![Value::Int64(0), Value::Null]]);
+    assert_eq!(fetch(&mut db, "SELECT region FROM sales WHERE id > 10 GROUP BY region").rows.len(), 0);
+}
+
+#[test]
+fn aggregates_are_refused_in_where() {
+    let mut db = sales();
+    assert!(matches!(run(&mut db, "SELECT id FROM sales WHERE COUNT(*) > 1"), Err(DbError::Execution(_))));
+}
+
+#[test]
+fn unknown_columns_are_reported_for_an_empty_group() {
+    let mut db = sales();
+    assert_eq!(run(&mut db, "SELECT COUNT(*), nosuch FROM sales WHERE id = 99").unwrap_err(), DbError::UnknownColumn("nosuch".to_string()));
+    assert_eq!(run(&mut db, "SELECT COUNT(*) FROM sales AS s WHERE id = 99 HAVING x.id > 1").unwrap_err(), DbError::UnknownColumn("x.id".to_string()));
+}
+
+#[test]
+fn columns_outside_group_by_and_aggregates_are_refused() {
+    let mut db = sales();
+    assert!(matches!(run(&mut db, "SELECT region, amount FROM sales GROUP BY region"), Err(DbError::Execution(message)) if message.contains("amount")));
+    assert!(matches!(run(&mut db, "SELECT COUNT(*), region FROM sales WHERE id = 99"), Err(DbError::Execution(_))));
+    assert!(matches!(run(&mut db, "SELECT region FROM sales GROUP BY region ORDER BY amount"), Err(DbError::Execution(_))));
+    assert!(matches!(run(&mut db, "SELECT region FROM sales GROUP BY region HAVING amount > 1"), Err(DbError::Execution(_))));
+}
+
+#[test]
+fn group_by_expressions_can_be_selected_whole() {
+    let mut db = sales();
+    let upper = fetch(&mut db, "SELECT UPPER(region), COUNT(*) FROM sales GROUP BY UPPER(region) ORDER BY 1");
+    assert_eq!(column(&upper, 0), vec![text("EAST"), text("NORTH"), text("SOUTH")]);
+    let shifted = fetch(&mut db, "SELECT id % 2 + 1, COUNT(*) FROM sales GROUP BY id % 2 + 1 ORDER BY 1");
+    assert_eq!(shifted.rows, vec![vec![Value::Int64(1), Value::Int64(2)], vec![Value::Int64(2), Value::Int64(3)]]);
+    let qualified = fetch(&mut db, "SELECT s.region, region, COUNT(*) FROM sales AS s GROUP BY region ORDER BY s.region LIMIT 1");
+    assert_eq!(qualified.rows, vec![vec![text("east"), text("east"), Value::Int64(1)]]);
+}
@@ -0,0 +1,41 @@
+#![allow(dead_code)]
+
+use in_memory_db::data_descriptor::DataBase;
+use in_memory_db::error::DbError;
+use in_memory_db::executor::{Execute, ResultSet, SuccessStatus};
+use in_memory_db::value::Value;
+use sqlparser::dialect::GenericDialect;
+use sqlparser::parser::Parser;
+
+/// Runs every statement of `sql` and returns the status of the last one.
+pub fn run(db: &mut DataBase, sql: &str) -> Result<SuccessStatus, DbError> {
+    let mut ret = Err(DbError::Parse("empty statement".to_string()));
+    for statement in Parser::parse_sql(&GenericDialect {}, sql).unwrap() {
+        ret = statement.execute(db);
+    }
+    ret
+}
+
+pub fn fetch(db: &mut DataBase, sql: &str) -> ResultSet {
+    match run(db, sql) {
+        Ok(SuccessStatus::DataFetched(data)) => bitcode::deserialize(&data).unwrap(),
+        other => panic!("expected rows, got {:?}", other),
+    }
+}
+
+/// The values of one column of a result, top to bottom.
+pub fn column(result: &ResultSet, position: usize) -> Vec<Value> {
+    result.rows.iter().map(|row| row[position].clone()).collect()
+}
+
+pub fn text(s: &str) -> Value {
+    Value::Text(s.to_string())
+}
+
+pub fn texts(values: &[&str]) -> Vec<Value> {
+    values.iter().map(|value| text(value)).collect()
+}
+
+pub fn ints(values: &[i64]) -> Vec<Value> {
+    values.iter().map(|value| Value::Int64(*value)).collect()
+}
@@ -0,0 +1,284 @@
+mod common;
+
+use common::{fetch, run};
+use in_memory_db::data_descriptor::DataBase;
+use in_memory_db::error::DbError;
+use in_memory_db::executor::SuccessStatus;
+use in_memory_db::value::Value;
+
+fn users() -> DataBase {
+    let mut db = DataBase::new();
+    run(&mut db, "CREATE TABLE users (id INT PRIMARY KEY, name VARCHAR(5), age INT, changed TIMESTAMP NULL ON UPDATE CURRENT_TIMESTAMP);
+        INSERT INTO users (id, name, age) VALUES (1, 'alice', 30), (2, 'bob', 40);").unwrap();
+    db
+}
+
+#[test]
+fn update_sets_values_and_rekeys_rows() {
+    let mut db = users();
+    assert!(matches!(run(&mut db, "UPDATE users SET age = age + 1, id = 10 WHERE name = 'bob'"), Ok(SuccessStatus::DataUpdated(1))));
+    let result = fetch(&mut db, "SELECT id, age, changed IS NOT NULL FROM users WHERE id = 10");
+    assert_eq!(result.rows, vec![vec![Value::Int64(10), Value::Int64(41), Value::Bool(true)]]);
+    assert!(matches!(run(&mut db, "UPDATE users SET age = 0"), Ok(SuccessStatus::DataUpdated(2))));
+}
+
+#[test]
+fn update_rejects_invalid_assignments_without_changes() {
+    let mut db = users();
+    assert!(run(&mut db, "UPDATE users SET name = 'too long name'").is_err());
+    assert!(run(&mut db, "UPDATE users SET missing = 1").is_err());
+    assert_eq!(fetch(&mut db, "SELECT * FROM users WHERE name <> 'too long name'").rows.len(), 2);
+}
+
+fn measures() -> DataBase {
+    let mut db = DataBase::new();
+    run(&mut db, "CREATE TABLE measures (id INT PRIMARY KEY, f FLOAT8, d DECIMAL(10,2));
+        INSERT INTO measures (id, f, d) VALUES (1, 1.5, 1.5);").unwrap();
+    db
+}
+
+#[test]
+fn update_stores_float_arithmetic() {
+    let mut db = measures();
+    assert!(matches!(run(&mut db, "UPDATE measures SET f = f * 1.1"), Ok(SuccessStatus::DataUpdated(1))));
+    assert_eq!(fetch(&mut db, "SELECT f FROM measures").rows, vec![vec![Value::Float64(1.5 * 1.1)]]);
+}
+
+#[test]
+fn update_rounds_decimal_arithmetic_to_the_column_scale() {
+    let mut db = measures();
+    assert!(matches!(run(&mut db, "UPDATE measures SET d = d * 1.1"), Ok(SuccessStatus::DataUpdated(1))));
+    assert!(matches!(run(&mut db, "UPDATE measures SET d = d / 3"), Ok(SuccessStatus::DataUpdated(1))));
+    assert_eq!(fetch(&mut db, "SELECT CAST(d AS VARCHAR(10)) FROM measures").rows, vec![vec![Value::Text("0.55".to_string())]]);
+    assert!(matches!(run(&mut db, "UPDATE measures SET d = d * 1e12"), Err(DbError::TypeMismatch { .. })));
+}
+
+#[test]
+fn update_accepts_the_table_name_or_alias_as_qualifier() {
+    let mut db = users();
+    assert!(matches!(run(&mut db, "UPDATE users SET users.age = users.age + 1 WHERE users.id = 1"), Ok(SuccessStatus::DataUpdated(1))));
+    assert!(matches!(run(&mut db, "UPDATE users AS u SET u.age = u.age + 1 WHERE u.id = 1"), Ok(SuccessStatus::DataUpdated(1))));
+    assert_eq!(fetch(&mut db, "SELECT age FROM users WHERE id = 1").rows, vec![vec![Value::Int64(32)]]);
+}
+
+#[test]
+fn update_rejects_other_qualifiers() {
+    let mut db = users();
+    assert_eq!(run(&mut db, "UPDATE users SET zz.age = 5").unwrap_err(), DbError::UnknownColumn("zz.age".to_string()));
+    assert_eq!(run(&mut db, "UPDATE users SET age = zz.age").unwrap_err(), DbError::UnknownColumn("zz.age".to_string()));
+    assert_eq!(run(&mut db, "UPDATE users SET age = 5 WHERE zz.id = 1").unwrap_err(), DbError::UnknownColumn("zz.id".to_string()));
+    assert_eq!(run(&mut db, "UPDATE users AS u SET age = 5 WHERE users.id = 1").unwrap_err(), DbError::UnknownColumn("users.id".to_string()));
+    assert_eq!(fetch(&mut db, "SELECT age FROM users ORDER BY id").rows, vec![vec![Value::Int64(30)], vec![Value::Int64(40)]]);
+}
+
+fn forum() -> DataBase {
+    let mut db = DataBase::new();
+    run(&mut db, "CREATE TABLE users (id INT PRIMARY KEY, name VARCHAR(20));
+        CREATE TABLE posts (id INT PRIMARY KEY, user_id INT REFERENCES users(id) ON DELETE CASCADE);
+        CREATE TABLE comments (id INT PRIMARY KEY, post_id INT NULL REFERENCES posts(id) ON DELETE SET NULL);
+        CREATE TABLE badges (id INT PRIMARY KEY, user_id INT REFERENCES users(id) ON DELETE RESTRICT);
+        INSERT INTO users (id, name) VALUES (1, 'alice'), (2, 'bob'), (3, 'carol');
+        INSERT INTO posts (id, user_id) VALUES (10, 1), (11, 1), (12, 2);
+        INSERT INTO comments (id, post_id) VALUES (100, 10), (101, 12);
+        INSERT INTO badges (id, user_id) VALUES (1000, 3);").unwrap();
+    db
+}
+
+#[test]
+fn delete_cascades_and_sets_null_through_foreign_keys() {
+    let mut db = forum();
+    assert!(matches!(run(&mut db, "DELETE FROM users WHERE id = 1"), Ok(SuccessStatus::DataDeleted(1))));
+    assert_eq!(fetch(&mut db, "SELECT id FROM posts").rows, vec![vec![Value::Int64(12)]]);
+    assert_eq!(fetch(&mut db, "SELECT * FROM comments WHERE post_id IS NULL").rows, vec![vec![Value::Int64(100), Value::Null]]);
+}
+
+#[test]
+fn delete_is_refused_by_a_restricting_foreign_key() {
+    let mut db = forum();
+    assert!(matches!(run(&mut db, "DELETE FROM users WHERE id = 3"), Err(DbError::ConstraintViolation { .. })));
+    assert_eq!(fetch(&mut db, "SELECT * FROM users").rows.len(), 3);
+}
+
+#[test]
+fn delete_without_where_removes_every_row() {
+    let mut db = forum();
+    assert!(matches!(run(&mut db, "DELETE FROM comments"), Ok(SuccessStatus::DataDeleted(2))));
+    assert!(fetch(&mut db, "SELECT * FROM comments").rows.is_empty());
+}
+
+#[test]
+fn delete_leaves_every_row_when_a_set_default_repeats_a_unique_value() {
+    let mut db = DataBase::new();
+    run(&mut db, "CREATE TABLE p2 (id INT PRIMARY KEY);
+        CREATE TABLE c2 (id INT PRIMARY KEY, pid INT UNIQUE DEFAULT 5 REFERENCES p2(id) ON DELETE SET DEFAULT);
+        INSERT INTO p2 (id) VALUES (1), (2);
+        INSERT INTO c2 (id, pid) VALUES (10, 1), (11, 2);").unwrap();
+    assert!(matches!(run(&mut db, "DELETE FROM p2"), Err(DbError::ConstraintViolation { constraint, .. }) if constraint.contains("pid")));
+    assert_eq!(fetch(&mut db, "SELECT id, pid FROM c2 ORDER BY id").rows,
+        vec![vec![Value::Int64(10), Value::Int64(1)], vec![Value::Int64(11), Value::Int64(2)]]);
+    assert_eq!(fetch(&mut db, "SELECT id FROM p2").rows.len(), 2);
+}
+
+#[test]
+fn delete_refuses_to_set_null_in_a_not_null_column() {
+    let mut db = DataBase::new();
+    run(&mut db, "CREATE TABLE p (id INT PRIMARY KEY);
+        CREATE TABLE c (id INT PRIMARY KEY, pid INT NOT NULL REFERENCES p(id) ON DELETE SET NULL);
+        INSERT INTO p (id) VALUES (1);
+        INSERT INTO c (id, pid) VALUES (10, 1);").unwrap();
+    assert!(matches!(run(&mut db, "DELETE FROM p"), Err(DbError::ConstraintViolation { constraint, .. }) if constraint == "NOT NULL pid"));
+    assert_eq!(fetch(&mut db, "SELECT pid FROM c").rows, vec![vec![Value::Int64(1)]]);
+    assert_eq!(fetch(&mut db, "SELECT id FROM p").rows.len(), 1);
+}
+
+#[test]
+fn delete_checks_qualifiers_against_the_table_name_or_alias() {
+    let mut db = forum();
+    assert_eq!(run(&mut db, "DELETE FROM comments WHERE zzz.id = 100").unwrap_err(), DbError::UnknownColumn("zzz.id".to_string()));
+    assert!(matches!(run(&mut db, "DELETE FROM comments WHERE comments.id = 100"), Ok(SuccessStatus::DataDeleted(1))));
+    assert!(matches!(run(&mut db, "DELETE FROM comments AS c WHERE c.id = 101"), Ok(SuccessStatus::DataDeleted(1))));
+}
+
+#[test]
+fn delete_runs_subqueries() {
+    let mut db = DataBase::new();
+    run(&mut db, "CREATE TABLE f (id INT); INSERT INTO f (id) VALUES (1), (2), (3);").unwrap();
+    assert!(matches!(run(&mut db, "DELETE FROM f WHERE id IN (SELECT 1)"), Ok(SuccessStatus::DataDeleted(1))));
+    assert!(matches!(run(&mut db, "DELETE FROM f AS x WHERE EXISTS (SELECT 1 FROM f WHERE f.id > x.id)"), Ok(SuccessStatus::DataDeleted(1))));
+    assert_eq!(fetch(&mut db, "SELECT id FROM f").rows, vec![vec![Value::Int64(3)]]);
+    assert_eq!(run(&mut db, "DELETE FROM f AS x WHERE EXISTS (SELECT 1 FROM f AS y WHERE f.id = y.id)").unwrap_err(), DbError::UnknownColumn("f.id".to_string()));
+}
+
+#[test]
+fn update_runs_subqueries_against_the_rows_before_the_update() {
+    let mut db = users();
+    let updated = run(&mut db, "UPDATE users AS u SET age = (SELECT MAX(age) FROM users) + u.id WHERE id IN (SELECT id FROM users WHERE age < 35)");
+    assert!(matches!(updated, Ok(SuccessStatus::DataUpdated(1))));
+    assert!(matches!(run(&mut db, "UPDATE users SET age = (SELECT MIN(age) FROM users)"), Ok(SuccessStatus::DataUpdated(2))));
+    assert_eq!(fetch(&mut db, "SELECT id, age FROM users ORDER BY id").rows, vec![
+        vec![Value::Int64(1), Value::Int64(40)],
+        vec![Value::Int64(2), Value::Int64(40)],
+    ]);
+}
+
+#[test]
+fn insert_reports_the_rejected_value() {
+    let mut db = DataBase::new();
+    run(&mut db, "CREATE TABLE users (id INT PRIMARY KEY, name VARCHAR(5), born DATE NULL)").unwrap();
+    let err = run(&mut db, "INSERT INTO users (id, name) VALUES (1, 'ann'), (2, 'too long')").unwrap_err();
+    assert_eq!(err.to_string(), "Value 'too long' is not valid for column name VARCHAR(5): longer than 5 characters");
+    assert!(fetch(&mut db, "SELECT * FROM users").rows.is_empty());
+}
+
+#[test]
+fn insert_reports_the_type_of_the_column() {
+    let mut db = DataBase::new();
+    run(&mut db, "CREATE TABLE users (id INT PRIMARY KEY, name VARCHAR(5), born DATE NULL)").unwrap();
+    assert!(matches!(run(&mut db, "INSERT INTO users (id, born) VALUES (3, '2024-13-01')"),
+        Err(DbError::TypeMismatch { column, data_type, .. }) if column == "born" && data_type == "DATE"));
+    assert!(fetch(&mut db, "SELECT * FROM users").rows.is_empty());
+}
+
+fn members() -> DataBase {
+    let mut db = DataBase::new();
+    run(&mut db, "CREATE TABLE users (id BIGINT PRIMARY KEY AUTO_INCREMENT, name VARCHAR(20) NOT NULL, role VARCHAR(10) DEFAULT 'member', note TEXT)").unwrap();
+    db
+}
+
+#[test]
+fn insert_fills_defaults_and_auto_increment() {
+    let mut db = members();
+    run(&mut db, "INSERT INTO users (name) VALUES ('alice'), ('bob')").unwrap();
+    run(&mut db, "INSERT INTO users (id, name, role) VALUES (10, 'carol', 'admin')").unwrap();
+    run(&mut db, "INSERT INTO users (id, name) VALUES (NULL, 'dave')").unwrap();
+    let mut rows = fetch(&mut db, "SELECT id, role, note FROM users WHERE name = 'bob' OR name = 'dave' OR id = 10").rows;
+    rows.sort();
+    assert_eq!(rows, vec![
+        vec![Value::Int64(2), Value::Text("member".to_string()), Value::Null],
+        vec![Value::Int64(10), Value::Text("admin".to_string()), Value::Null],
+        vec![Value::Int64(11), Value::Text("member".to_string()), Value::Null],
+    ]);
+}
+
+#[test]
+fn not_null_columns_refuse_null() {
+    let mut db = members();
+    run(&mut db, "INSERT INTO users (name) VALUES ('alice')").unwrap();
+    assert!(matches!(run(&mut db, "INSERT INTO users (role) VALUES ('guest')"),
+        Err(DbError::ConstraintViolation { constraint, .. }) if constraint == "NOT NULL name"));
+    assert!(matches!(run(&mut db, "UPDATE users SET name = NULL"), Err(DbError::ConstraintViolation { .. })));
+}
+
+fn pairs() -> DataBase {
+    let mut db = DataBase::new();
+    run(&mut db, "CREATE TABLE pairs (a VARCHAR(10), b VARCHAR(10), email VARCHAR(50) UNIQUE, PRIMARY KEY (a, b))").unwrap();
+    run(&mut db, "INSERT INTO pairs (a, b, email) VALUES ('1', '2_3', 'x@example.com'), ('1_2', '3', NULL), ('1', '3', NULL)").unwrap();
+    db
+}
+
+#[test]
+fn composite_primary_keys_do_not_mix_up_their_parts() {
+    let mut db = pairs();
+    assert_eq!(fetch(&mut db, "SELECT a FROM pairs").rows.len(), 3);
+}
+
+#[test]
+fn insert_rejects_duplicate_keys() {
+    let mut db = pairs();
+    assert!(matches!(run(&mut db, "INSERT INTO pairs (a, b) VALUES ('1', '2_3')"),
+        Err(DbError::ConstraintViolation { constraint, .. }) if constraint == "PRIMARY KEY (a, b)"));
+    assert!(matches!(run(&mut db, "INSERT INTO pairs (a, b) VALUES ('5', '5'), ('5', '5')"), Err(DbError::ConstraintViolation { .. })));
+    assert!(matches!(run(&mut db, "INSERT INTO pairs (a, b, email) VALUES ('7', '7', 'x@example.com')"),
+        Err(DbError::ConstraintViolation { constraint, .. }) if constraint == "UNIQUE email"));
+    assert_eq!(fetch(&mut db, "SELECT a FROM pairs").rows.len(), 3);
+}
+
+#[test]
+fn update_rejects_duplicate_keys() {
+    let mut db = pairs();
+    assert!(matches!(run(&mut db, "UPDATE pairs SET b = '2_3' WHERE a = '1'"), Err(DbError::ConstraintViolation { .. })));
+    assert!(matches!(run(&mut db, "UPDATE pairs SET email = 'y@example.com'"), Err(DbError::ConstraintViolation { .. })));
+    assert!(matches!(run(&mut db, "UPDATE pairs SET email = 'y@example.com' WHERE b = '2_3'"), Ok(SuccessStatus::DataUpdated(1))));
+    assert_eq!(fetch(&mut db, "SELECT a FROM pairs").rows.len(), 3);
+}
+
+#[test]
+fn tables_without_keys_accept_repeated_rows() {
+    let mut db = DataBase::new();
+    run(&mut db, "CREATE TABLE events (name TEXT)").unwrap();
+    run(&mut db, "INSERT INTO events (name) VALUES ('a'), ('a')").unwrap();
+    run(&mut db, "INSERT INTO events (name) VALUES ('b')").unwrap();
+    assert_eq!(fetch(&mut db, "SELECT name FROM events").rows.len(), 3);
+}
+
+#[test]
+fn insert_converts_computed_values_and_defaults_to_the_column_type() {
+    let mut db = DataBase::new();
+    run(&mut db, "CREATE TABLE prices (id INT PRIMARY KEY, f FLOAT8, d DECIMAL(10,2), fee DECIMAL(6,2) DEFAULT 10.0 / 3)").unwrap();
+    run(&mut db, "INSERT INTO prices (id, f, d) VALUES (1, CAST(1.5 AS FLOAT8) * 1.1, 1.5 * 1.1)").unwrap();
+    let result = fetch(&mut db, "SELECT f, CAST(d AS VARCHAR(10)), CAST(fee AS VARCHAR(10)) FROM prices");
+    assert_eq!(result.rows, vec![vec![Value::Float64(1.5 * 1.1), Value::Text("1.65".to_string()), Value::Text("3.33".to_string())]]);
+}
+
+#[test]
+fn insert_select_stores_the_rows_of_the_query() {
+    let mut db = members();
+    run(&mut db, "INSERT INTO users (name) VALUES ('alice'), ('bob')").unwrap();
+    assert!(matches!(run(&mut db, "INSERT INTO users (id) SELECT 4"), Err(DbError::ConstraintViolation { .. })));
+    assert!(matches!(run(&mut db, "INSERT INTO users (name, note) SELECT UPPER(name), 'copy' FROM users ORDER BY name"), Ok(SuccessStatus::DataInserted)));
+    let copies = fetch(&mut db, "SELECT id, name, role FROM users WHERE note = 'copy' ORDER BY id");
+    assert_eq!(copies.rows, vec![
+        vec![Value::Int64(3), Value::Text("ALICE".to_string()), Value::Text("member".to_string())],
+        vec![Value::Int64(4), Value::Text("BOB".to_string()), Value::Text("member".to_string())],
+    ]);
+    assert!(matches!(run(&mut db, "INSERT INTO users (name) SELECT name, note FROM users"), Err(DbError::Execution(_))));
+    assert_eq!(fetch(&mut db, "SELECT COUNT(*) FROM users").rows, vec![vec![Value::Int64(4)]]);
+}
+
+#[test]
+fn insert_select_of_a_constant_stores_a_row() {
+    let mut db = DataBase::new();
+    run(&mut db, "CREATE TABLE f (id INT)").unwrap();
+    run(&mut db, "INSERT INTO f (id) SELECT 4").unwrap();
+    assert_eq!(fetch(&mut db, "SELECT id FROM f").rows, vec![vec![Value::Int64(4)]]);
+}
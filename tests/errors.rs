@@ -0,0 +1,38 @@
+mod common;
+
+use common::run;
+use in_memory_db::data_descriptor::DataBase;
+use in_memory_db::error::DbError;
+
+fn users() -> DataBase {
+    let mut db = DataBase::new();
+    run(&mut db, "CREATE TABLE users (id INT PRIMARY KEY, name VARCHAR(5));
+        INSERT INTO users (id, name) VALUES (1, 'alice');").unwrap();
+    db
+}
+
+#[test]
+fn unknown_tables_and_columns_are_named() {
+    let mut db = users();
+    assert_eq!(run(&mut db, "SELECT * FROM nobody").unwrap_err(), DbError::UnknownTable("nobody".to_string()));
+    assert_eq!(run(&mut db, "INSERT INTO users (id, age) VALUES (2, 3)").unwrap_err(), DbError::UnknownColumn("age".to_string()));
+    assert_eq!(run(&mut db, "SELECT id FROM users WHERE nope = 1").unwrap_err(), DbError::UnknownColumn("nope".to_string()));
+}
+
+#[test]
+fn type_mismatches_name_the_column() {
+    let mut db = users();
+    assert!(matches!(run(&mut db, "UPDATE users SET name = 'much too long'"), Err(DbError::TypeMismatch { column, .. }) if column == "name"));
+}
+
+#[test]
+fn failures_while_running_are_execution_errors() {
+    let mut db = users();
+    assert!(matches!(run(&mut db, "SELECT id / 0 FROM users"), Err(DbError::Execution(_))));
+}
+
+#[test]
+fn unsupported_statements_are_reported_as_such() {
+    let mut db = users();
+    assert!(matches!(run(&mut db, "DROP TABLE users"), Err(DbError::Unsupported(_))));
+}
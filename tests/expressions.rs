@@ -0,0 +1,132 @@
+mod common;
+
+use common::{column, fetch, ints, run, texts};
+use in_memory_db::data_descriptor::DataBase;
+use in_memory_db::error::DbError;
+use in_memory_db::value::Value;
+
+fn users() -> DataBase {
+    let mut db = DataBase::new();
+    run(&mut db, "CREATE TABLE users (id INT PRIMARY KEY, name VARCHAR(20), age INT NULL);
+        INSERT INTO users (id, name, age) VALUES (1, 'alice', 30), (2, 'bob', NULL), (3, 'carol', 45), (4, 'dave', 18);").unwrap();
+    db
+}
+
+fn count(db: &mut DataBase, sql: &str) -> usize {
+    fetch(db, sql).rows.len()
+}
+
+#[test]
+fn where_compares_and_combines_conditions() {
+    let mut db = users();
+    assert_eq!(count(&mut db, "SELECT * FROM users WHERE age > 20"), 2);
+    assert_eq!(count(&mut db, "SELECT * FROM users WHERE age IS NULL OR name = 'dave'"), 2);
+}
+
+#[test]
+fn where_supports_between_in_and_like() {
+    let mut db = users();
+    assert_eq!(count(&mut db, "SELECT * FROM users WHERE NOT age BETWEEN 18 AND 30"), 1);
+    assert_eq!(count(&mut db, "SELECT * FROM users WHERE id IN (1, 3, 9) AND name LIKE '%o%'"), 1);
+}
+
+#[test]
+fn not_in_with_a_null_matches_nothing() {
+    let mut db = users();
+    assert_eq!(count(&mut db, "SELECT * FROM users WHERE age NOT IN (30, NULL)"), 0);
+}
+
+#[test]
+fn where_rejects_unknown_columns() {
+    let mut db = users();
+    assert_eq!(run(&mut db, "SELECT * FROM users WHERE missing = 1").unwrap_err(), DbError::UnknownColumn("missing".to_string()));
+}
+
+#[test]
+fn negating_the_smallest_decimal_overflows() {
+    let mut db = DataBase::new();
+    assert_eq!(run(&mut db, "SELECT -(CAST('-92233720368547758.07' AS DECIMAL(19,2)) - CAST('0.01' AS DECIMAL(3,2)))").unwrap_err(),
+        DbError::Execution("numeric overflow".to_string()));
+    assert_eq!(fetch(&mut db, "SELECT -CAST('-92233720368547758.07' AS DECIMAL(19,2))").rows[0][0].to_string(), "92233720368547758.07");
+}
+
+/// The values of the first row of `sql`, as displayed.
+fn first_row(db: &mut DataBase, sql: &str) -> Vec<String> {
+    fetch(db, sql).rows[0].iter().map(|value| value.to_string()).collect()
+}
+
+#[test]
+fn cast_converts_between_types() {
+    let mut db = DataBase::new();
+    assert_eq!(first_row(&mut db, "SELECT CAST('42' AS INT), CAST(3.7 AS INTEGER), CAST(12 AS VARCHAR(5)), CAST('2024-05-17' AS TIMESTAMP), \
+        CAST(TO_TIMESTAMP('2024-05-17 13:45:00') AS DATE), CAST(2.345 AS DECIMAL(5, 2)), CAST('yes' AS BOOLEAN), '7'::BIGINT + 1, DATE '2024-02-29', CAST(1 AS FLOAT)"),
+        vec!["42", "4", "12", "2024-05-17 00:00:00", "2024-05-17", "2.35", "true", "8", "2024-02-29", "1"]);
+}
+
+#[test]
+fn try_cast_gives_null_where_cast_fails() {
+    let mut db = DataBase::new();
+    assert_eq!(fetch(&mut db, "SELECT CAST('42' AS INT), TRY_CAST('abc' AS INT), TRY_CAST('2024-13-01' AS DATE)").rows,
+        vec![vec![Value::Int64(42), Value::Null, Value::Null]]);
+    assert!(matches!(run(&mut db, "SELECT CAST('abc' AS INT)"), Err(DbError::Execution(_))));
+}
+
+#[test]
+fn logic_with_nulls_is_three_valued() {
+    let mut db = DataBase::new();
+    assert_eq!(first_row(&mut db, "SELECT NULL AND FALSE, NULL OR TRUE, NULL AND TRUE, NOT (NULL = 1), (NULL = 1) IS UNKNOWN"), vec!["false", "true", "NULL", "NULL", "true"]);
+}
+
+fn scores() -> DataBase {
+    let mut db = DataBase::new();
+    run(&mut db, "CREATE TABLE scores (id INT, score INT)").unwrap();
+    run(&mut db, "INSERT INTO scores (id, score) VALUES (1, 95), (2, 70), (3, NULL), (4, CAST('40' AS INT))").unwrap();
+    db
+}
+
+#[test]
+fn searched_and_simple_case() {
+    let mut db = scores();
+    let graded = fetch(&mut db, "SELECT CASE WHEN score >= 90 THEN 'A' WHEN score >= 60 THEN 'B' ELSE 'F' END, \
+        CASE score WHEN 95 THEN 'top' WHEN NULL THEN 'null' END FROM scores ORDER BY id");
+    assert_eq!(column(&graded, 0), texts(&["A", "B", "F", "F"]));
+    assert_eq!(column(&graded, 1), vec![Value::Text("top".to_string()), Value::Null, Value::Null, Value::Null]);
+}
+
+#[test]
+fn unknown_conditions_filter_rows_out() {
+    let mut db = scores();
+    assert_eq!(column(&fetch(&mut db, "SELECT id FROM scores WHERE NOT (score > 80) ORDER BY id"), 0), ints(&[2, 4]));
+    assert_eq!(column(&fetch(&mut db, "SELECT id FROM scores WHERE CASE WHEN score IS NULL THEN TRUE ELSE score < 50 END ORDER BY id"), 0), ints(&[3, 4]));
+}
+
+#[test]
+fn case_and_cast_in_updates_and_inserts() {
+    let mut db = scores();
+    run(&mut db, "UPDATE scores SET score = CASE WHEN score IS NULL THEN 0 ELSE CAST(score * 1.1 AS INT) END").unwrap();
+    run(&mut db, "INSERT INTO scores (id, score) VALUES (5, CASE WHEN 1 > 2 THEN 1 ELSE 2 END)").unwrap();
+    assert_eq!(column(&fetch(&mut db, "SELECT score FROM scores ORDER BY id"), 0), ints(&[105, 77, 0, 44, 2]));
+}
+
+#[test]
+fn cast_rounds_like_round() {
+    let mut db = DataBase::new();
+    assert_eq!(first_row(&mut db, "SELECT CAST(2.675 AS DECIMAL(5, 2)), ROUND(2.675, 2), CAST(CAST(2.675 AS FLOAT) AS DECIMAL(5, 2)), CAST(-2.5 AS INT), ROUND(-2.5)"),
+        vec!["2.68", "2.68", "2.68", "-3", "-3"]);
+    assert_eq!(first_row(&mut db, "SELECT ROUND(CAST(1.005 AS FLOAT), 2), CAST(CAST(1.005 AS FLOAT) AS DECIMAL(5, 2)), FLOOR(CAST(-1.5 AS FLOAT)), CEIL(CAST(1.2 AS FLOAT))"),
+        vec!["1.01", "1.01", "-2", "2"]);
+}
+
+#[test]
+fn cast_to_strings_of_a_length_cuts_or_pads() {
+    let mut db = DataBase::new();
+    assert_eq!(first_row(&mut db, "SELECT CAST('toolong' AS VARCHAR(3)), CAST('abc' AS CHAR), CAST('ab' AS CHAR(4)) || '|', CAST(123456 AS CHAR(3))"),
+        vec!["too", "a", "ab  |", "123"]);
+}
+
+#[test]
+fn cast_to_strings_without_a_length_takes_any_length() {
+    let mut db = DataBase::new();
+    let long = "x".repeat(100);
+    assert_eq!(first_row(&mut db, &format!("SELECT CAST('{long}' AS VARCHAR), CAST('{long}' AS TEXT)")), vec![long.clone(), long]);
+}
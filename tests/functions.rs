@@ -0,0 +1,60 @@
+mod common;
+
+use common::{fetch, run};
+use in_memory_db::data_descriptor::DataBase;
+use in_memory_db::error::DbError;
+use in_memory_db::value::Value;
+
+/// The values of the first row of `sql`, as displayed.
+fn first_row(db: &mut DataBase, sql: &str) -> Vec<String> {
+    fetch(db, sql).rows[0].iter().map(|value| value.to_string()).collect()
+}
+
+#[test]
+fn string_functions() {
+    let mut db = DataBase::new();
+    assert_eq!(first_row(&mut db, "SELECT LOWER('AbC'), UPPER('x'), LENGTH('h\u{e9}llo'), SUBSTRING('database', 5), SUBSTRING('database' FROM 2 FOR 3), SUBSTR('abc', 0, 2), \
+        TRIM('  pad  '), TRIM(LEADING 'x' FROM 'xxaxx'), RTRIM('axx', 'x'), CONCAT('a', NULL, 1), REPLACE('a-b-c', '-', '+'), POSITION('b' IN 'abc')"),
+        vec!["abc", "X", "5", "base", "ata", "a", "pad", "axx", "a", "a1", "a+b+c", "2"]);
+}
+
+#[test]
+fn numeric_functions() {
+    let mut db = DataBase::new();
+    assert_eq!(first_row(&mut db, "SELECT ABS(-3), ROUND(2.345, 2), ROUND(2.5), ROUND(1234, -2), FLOOR(-1.5), CEIL(1.2), MOD(7, 3), POWER(2, 10), POWER(4, 0.5)"),
+        vec!["3", "2.35", "3", "1200", "-2", "2", "1", "1024", "2"]);
+}
+
+#[test]
+fn null_handling_functions() {
+    let mut db = DataBase::new();
+    assert_eq!(first_row(&mut db, "SELECT COALESCE(NULL, NULL, 3), IFNULL(NULL, 'x'), NULLIF(1, 1), NULLIF(1, 2), UPPER(NULL)"), vec!["3", "x", "NULL", "1", "NULL"]);
+}
+
+#[test]
+fn date_and_time_functions() {
+    let mut db = DataBase::new();
+    assert_eq!(first_row(&mut db, "SELECT DATE_ADD('2024-01-31', INTERVAL 1 MONTH), DATE_SUB(TO_TIMESTAMP('2024-03-01 10:30:00'), INTERVAL '2 hours'), \
+        EXTRACT(YEAR FROM TO_DATE('2024-02-29')), DATE_PART('dow', '2024-06-02'), DATE_TRUNC('month', TO_TIMESTAMP('2024-05-17 13:45:00')), DATE_TRUNC('week', '2024-05-17'), \
+        TO_CHAR(TO_DATE('17/05/2024', '%d/%m/%Y'), '%Y.%m.%d'), TO_NUMBER(' 42 ')"),
+        vec!["2024-02-29", "2024-03-01 08:30:00", "2024", "0", "2024-05-01 00:00:00", "2024-05-13", "2024.05.17", "42"]);
+    assert!(matches!(fetch(&mut db, "SELECT NOW()").rows[0][0], Value::Timestamp(_)));
+}
+
+#[test]
+fn functions_in_inserts_updates_and_filters() {
+    let mut db = DataBase::new();
+    run(&mut db, "CREATE TABLE people (id INT, name VARCHAR(20), joined DATE)").unwrap();
+    run(&mut db, "INSERT INTO people (id, name, joined) VALUES (1, UPPER('ann'), DATE_ADD('2024-01-01', INTERVAL 10 DAY)), (-2, 'bo', '2024-03-05')").unwrap();
+    run(&mut db, "UPDATE people SET name = CONCAT(name, '!') WHERE LENGTH(name) = 3").unwrap();
+    assert_eq!(first_row(&mut db, "SELECT id, name, joined FROM people WHERE EXTRACT(MONTH FROM joined) = 1"), vec!["1", "ANN!", "2024-01-11"]);
+    assert_eq!(fetch(&mut db, "SELECT ABS(id) FROM people WHERE SUBSTRING(name, 1, 1) = 'b'").rows, vec![vec![Value::Int64(2)]]);
+}
+
+#[test]
+fn unknown_functions_and_bad_arguments_are_refused() {
+    let mut db = DataBase::new();
+    assert!(matches!(run(&mut db, "SELECT NO_SUCH_FUNCTION(1)"), Err(DbError::Unsupported(_))));
+    assert!(matches!(run(&mut db, "SELECT LOWER()"), Err(DbError::Execution(_))));
+    assert!(matches!(run(&mut db, "SELECT ROUND('x')"), Err(DbError::Execution(_))));
+}
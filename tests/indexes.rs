@@ -0,0 +1,138 @@
+mod common;
+
+use common::{fetch, run};
+use in_memory_db::data_descriptor::DataBase;
+use in_memory_db::error::DbError;
+use in_memory_db::executor::SuccessStatus;
+use in_memory_db::value::Value;
+
+/// First column of every row of `sql`, sorted, as index lookups do not promise an order.
+fn sorted_first_column(db: &mut DataBase, sql: &str) -> Vec<Value> {
+    let mut values: Vec<Value> = fetch(db, sql).rows.into_iter().map(|mut row| row.remove(0)).collect();
+    values.sort();
+    values
+}
+
+fn users() -> DataBase {
+    let mut db = DataBase::new();
+    run(&mut db, "CREATE TABLE users (id BIGINT PRIMARY KEY, email VARCHAR(50) UNIQUE, age INT)").unwrap();
+    run(&mut db, "INSERT INTO users (id, email, age) VALUES (1, 'a@x.com', 30), (2, 'b@x.com', 40), (3, 'c@x.com', 50), (4, NULL, 60)").unwrap();
+    db
+}
+
+#[test]
+fn indexed_columns_answer_equalities_ranges_and_lists() {
+    let mut db = users();
+    assert_eq!(sorted_first_column(&mut db, "SELECT id FROM users WHERE email = 'b@x.com'"), vec![Value::Int64(2)]);
+    assert_eq!(sorted_first_column(&mut db, "SELECT id FROM users WHERE id >= 2 AND id < 4"), vec![Value::Int64(2), Value::Int64(3)]);
+    assert_eq!(sorted_first_column(&mut db, "SELECT id FROM users WHERE id BETWEEN 3 AND 10 AND age > 55"), vec![Value::Int64(4)]);
+    assert_eq!(sorted_first_column(&mut db, "SELECT id FROM users WHERE email IN ('c@x.com', 'a@x.com', 'c@x.com')"), vec![Value::Int64(1), Value::Int64(3)]);
+    assert_eq!(sorted_first_column(&mut db, "SELECT id FROM users WHERE 2.5 < id"), vec![Value::Int64(3), Value::Int64(4)]);
+}
+
+#[test]
+fn indexes_follow_updates_deletes_and_inserts() {
+    let mut db = users();
+    run(&mut db, "UPDATE users SET email = 'z@x.com' WHERE id = 2").unwrap();
+    assert_eq!(sorted_first_column(&mut db, "SELECT id FROM users WHERE email = 'b@x.com'"), vec![]);
+    assert_eq!(sorted_first_column(&mut db, "SELECT id FROM users WHERE email = 'z@x.com'"), vec![Value::Int64(2)]);
+    run(&mut db, "DELETE FROM users WHERE email = 'z@x.com'").unwrap();
+    assert_eq!(sorted_first_column(&mut db, "SELECT id FROM users WHERE id > 1"), vec![Value::Int64(3), Value::Int64(4)]);
+    run(&mut db, "INSERT INTO users (id, email) VALUES (2, 'b@x.com')").unwrap();
+    assert_eq!(sorted_first_column(&mut db, "SELECT id FROM users WHERE email = 'b@x.com'"), vec![Value::Int64(2)]);
+}
+
+fn orders() -> DataBase {
+    let mut db = DataBase::new();
+    run(&mut db, "CREATE TABLE orders (id INT PRIMARY KEY, customer VARCHAR(20), day INT)").unwrap();
+    run(&mut db, "INSERT INTO orders (id, customer, day) VALUES (1, 'ann', 1), (2, 'bob', 1), (3, 'ann', 2)").unwrap();
+    db
+}
+
+fn index_names(db: &DataBase, table_name: &str) -> Vec<String> {
+    let table = db.get_table_ref(table_name).unwrap().lock().unwrap();
+    let mut names: Vec<String> = table.secondary_indexes().map(|index| index.name().to_string()).collect();
+    names.sort();
+    names
+}
+
+#[test]
+fn create_index_refuses_a_taken_name() {
+    let mut db = orders();
+    assert!(matches!(run(&mut db, "CREATE INDEX orders_customer_day ON orders USING HASH (customer, day)"),
+        Ok(SuccessStatus::IndexCreated(name)) if name == "orders_customer_day"));
+    assert!(matches!(run(&mut db, "CREATE INDEX orders_customer_day ON orders (day)"), Err(DbError::Execution(_))));
+    run(&mut db, "CREATE INDEX IF NOT EXISTS orders_customer_day ON orders (day)").unwrap();
+    assert_eq!(index_names(&db, "orders"), vec!["orders_customer_day", "orders_id_key"]);
+    assert_eq!(fetch(&mut db, "SELECT id FROM orders WHERE customer = 'ann' AND day = 2").rows, vec![vec![Value::Int64(3)]]);
+}
+
+#[test]
+fn unique_index_enforces_its_columns() {
+    let mut db = orders();
+    assert!(matches!(run(&mut db, "CREATE UNIQUE INDEX orders_day ON orders (day)"), Err(DbError::ConstraintViolation { .. })));
+    run(&mut db, "CREATE UNIQUE INDEX orders_customer ON orders (customer, day)").unwrap();
+    assert!(matches!(run(&mut db, "INSERT INTO orders (id, customer, day) VALUES (4, 'bob', 1)"),
+        Err(DbError::ConstraintViolation { constraint, .. }) if constraint == "orders_customer"));
+    run(&mut db, "INSERT INTO orders (id, customer, day) VALUES (4, 'bob', 2)").unwrap();
+    assert_eq!(fetch(&mut db, "SELECT id FROM orders WHERE customer = 'bob' AND day = 2").rows, vec![vec![Value::Int64(4)]]);
+}
+
+#[test]
+fn drop_index_lifts_its_constraint() {
+    let mut db = orders();
+    run(&mut db, "CREATE UNIQUE INDEX orders_customer ON orders (customer, day)").unwrap();
+    run(&mut db, "DROP INDEX orders_customer").unwrap();
+    run(&mut db, "INSERT INTO orders (id, customer, day) VALUES (5, 'bob', 1)").unwrap();
+    assert!(matches!(run(&mut db, "DROP INDEX orders_customer"), Err(DbError::Execution(_))));
+    run(&mut db, "DROP INDEX IF EXISTS orders_customer").unwrap();
+}
+
+#[test]
+fn implicit_key_indexes_leave_column_names_free() {
+    let mut db = DataBase::new();
+    run(&mut db, "CREATE TABLE a (id INT PRIMARY KEY, n INT); CREATE TABLE b (id INT PRIMARY KEY)").unwrap();
+    assert!(matches!(run(&mut db, "CREATE INDEX id ON a (id, n)"), Ok(SuccessStatus::IndexCreated(name)) if name == "id"));
+    assert_eq!(index_names(&db, "a"), vec!["a_id_key", "id"]);
+    run(&mut db, "DROP INDEX id").unwrap();
+    assert_eq!(index_names(&db, "a"), vec!["a_id_key"]);
+    assert_eq!(index_names(&db, "b"), vec!["b_id_key"]);
+}
+
+#[test]
+fn drop_index_refuses_indexes_backing_constraints() {
+    let mut db = DataBase::new();
+    run(&mut db, "CREATE TABLE a (id INT PRIMARY KEY, email VARCHAR(20) UNIQUE)").unwrap();
+    assert!(matches!(run(&mut db, "DROP INDEX a_id_key"), Err(DbError::Execution(_))));
+    assert!(matches!(run(&mut db, "DROP INDEX \"UNIQUE email\""), Err(DbError::Execution(_))));
+    assert_eq!(index_names(&db, "a"), vec!["UNIQUE email", "a_id_key"]);
+}
+
+fn stock() -> DataBase {
+    let mut db = DataBase::new();
+    run(&mut db, "CREATE TABLE stock (shop INT, item VARCHAR(10), qty INT, PRIMARY KEY (shop, item))").unwrap();
+    run(&mut db, "INSERT INTO stock (shop, item, qty) VALUES (1, 'nail', 10), (1, 'screw', 20), (2, 'nail', 30), (3, 'nail', 40)").unwrap();
+    db
+}
+
+#[test]
+fn primary_key_lookups_find_single_rows_and_lists() {
+    let mut db = stock();
+    assert_eq!(sorted_first_column(&mut db, "SELECT qty FROM stock WHERE shop = 1 AND item = 'screw'"), vec![Value::Int64(20)]);
+    assert_eq!(sorted_first_column(&mut db, "SELECT qty FROM stock WHERE item = 'nail' AND shop IN (3, 1, 3, 7)"), vec![Value::Int64(10), Value::Int64(40)]);
+    assert_eq!(sorted_first_column(&mut db, "SELECT qty FROM stock WHERE shop = 1 AND item = 'bolt'"), vec![]);
+}
+
+#[test]
+fn primary_key_lookups_still_apply_the_rest_of_the_condition() {
+    let mut db = stock();
+    assert_eq!(sorted_first_column(&mut db, "SELECT qty FROM stock WHERE shop = 2 AND item = 'nail' AND qty > 50"), vec![]);
+}
+
+#[test]
+fn update_and_delete_use_primary_key_lookups() {
+    let mut db = stock();
+    assert!(matches!(run(&mut db, "UPDATE stock SET qty = qty + 1 WHERE shop = 2 AND item = 'nail'"), Ok(SuccessStatus::DataUpdated(1))));
+    assert!(matches!(run(&mut db, "DELETE FROM stock WHERE shop IN (1, 2) AND item = 'nail'"), Ok(SuccessStatus::DataDeleted(2))));
+    assert_eq!(sorted_first_column(&mut db, "SELECT qty FROM stock"), vec![Value::Int64(20), Value::Int64(40)]);
+}
@@ -0,0 +1,141 @@
+mod common;
+
+use common::{column, fetch, run, text};
+use in_memory_db::data_descriptor::DataBase;
+use in_memory_db::error::DbError;
+use in_memory_db::value::Value;
+
+fn company() -> DataBase {
+    let mut db = DataBase::new();
+    run(&mut db, "CREATE TABLE depts (id INT PRIMARY KEY, name VARCHAR(10))").unwrap();
+    run(&mut db, "CREATE TABLE staff (id INT PRIMARY KEY, name VARCHAR(10), dept_id INT, salary INT)").unwrap();
+    run(&mut db, "CREATE TABLE bands (grade VARCHAR(1), low INT, high INT)").unwrap();
+    run(&mut db, "INSERT INTO depts (id, name) VALUES (1, 'sales'), (2, 'ops'), (3, 'legal')").unwrap();
+    run(&mut db, "INSERT INTO staff (id, name, dept_id, salary) VALUES (10, 'amy', 1, 300), (11, 'bob', 1, 150), (12, 'cid', 2, 200), (13, 'dan', NULL, 100)").unwrap();
+    run(&mut db, "INSERT INTO bands (grade, low, high) VALUES ('a', 250, 400), ('b', 120, 249), ('c', 0, 119)").unwrap();
+    run(&mut db, "CREATE TABLE managers (dept_id INT, boss VARCHAR(10))").unwrap();
+    run(&mut db, "INSERT INTO managers (dept_id, boss) VALUES (1, 'amy'), (3, 'eve')").unwrap();
+    db
+}
+
+#[test]
+fn inner_join_keeps_matching_pairs() {
+    let mut db = company();
+    let inner = fetch(&mut db, "SELECT s.name, d.name AS dept FROM staff s JOIN depts AS d ON s.dept_id = d.id WHERE salary > 120 ORDER BY s.id");
+    assert_eq!(inner.columns, vec!["name", "dept"]);
+    assert_eq!(inner.rows, vec![vec![text("amy"), text("sales")], vec![text("bob"), text("sales")], vec![text("cid"), text("ops")]]);
+}
+
+#[test]
+fn outer_joins_pad_unmatched_rows_with_nulls() {
+    let mut db = company();
+    let left = fetch(&mut db, "SELECT staff.name, depts.name FROM staff LEFT JOIN depts ON staff.dept_id = depts.id AND depts.name <> 'ops' ORDER BY staff.id");
+    assert_eq!(column(&left, 1), vec![text("sales"), text("sales"), Value::Null, Value::Null]);
+
+    let right = fetch(&mut db, "SELECT d.name, COUNT(s.id) FROM staff s RIGHT JOIN depts d ON s.dept_id = d.id GROUP BY d.name ORDER BY d.name");
+    assert_eq!(right.rows, vec![vec![text("legal"), Value::Int64(0)], vec![text("ops"), Value::Int64(1)], vec![text("sales"), Value::Int64(2)]]);
+
+    let full = fetch(&mut db, "SELECT s.id, d.id FROM staff s FULL OUTER JOIN depts d ON s.dept_id = d.id ORDER BY s.id NULLS FIRST, d.id");
+    assert_eq!(full.rows.len(), 5);
+    assert_eq!(full.rows[0], vec![Value::Null, Value::Int64(3)]);
+    assert_eq!(full.rows[4], vec![Value::Int64(13), Value::Null]);
+}
+
+#[test]
+fn using_merges_the_join_columns() {
+    let mut db = company();
+    let using = fetch(&mut db, "SELECT * FROM staff JOIN managers USING (dept_id) ORDER BY id");
+    assert_eq!(using.columns, vec!["dept_id", "id", "name", "salary", "boss"]);
+    assert_eq!(using.rows[1], vec![Value::Int64(1), Value::Int64(11), text("bob"), Value::Int64(150), text("amy")]);
+    let merged = fetch(&mut db, "SELECT dept_id, boss FROM staff FULL JOIN managers USING (dept_id) WHERE staff.id IS NULL OR dept_id IS NULL ORDER BY dept_id");
+    assert_eq!(merged.rows, vec![vec![Value::Int64(3), text("eve")], vec![Value::Null, Value::Null]]);
+}
+
+#[test]
+fn joins_on_non_equalities_and_chained_joins() {
+    let mut db = company();
+    let graded = fetch(&mut db, "SELECT s.name, b.grade, d.name FROM staff s JOIN bands b ON s.salary BETWEEN b.low AND b.high \
+        LEFT JOIN depts d ON d.id = s.dept_id ORDER BY s.name");
+    assert_eq!(column(&graded, 1), vec![text("a"), text("b"), text("b"), text("c")]);
+    assert_eq!(graded.rows[3][2], Value::Null);
+}
+
+#[test]
+fn column_references_must_resolve_to_one_joined_relation() {
+    let mut db = company();
+    assert!(matches!(run(&mut db, "SELECT name FROM staff JOIN depts ON staff.dept_id = depts.id"), Err(DbError::Execution(_))));
+    assert!(matches!(run(&mut db, "SELECT x.name FROM staff s JOIN depts d ON s.dept_id = d.id"), Err(DbError::UnknownColumn(_))));
+    assert!(matches!(run(&mut db, "SELECT s.name FROM staff AS s WHERE staff.id = 10"), Err(DbError::UnknownColumn(_))));
+    assert_eq!(fetch(&mut db, "SELECT s.name FROM staff AS s WHERE s.id = 10").rows, vec![vec![text("amy")]]);
+}
+
+fn dated() -> DataBase {
+    let mut db = DataBase::new();
+    run(&mut db, "CREATE TABLE d1 (id INT PRIMARY KEY, d DATE);
+        CREATE TABLE d2 (id INT PRIMARY KEY, s VARCHAR(20));
+        INSERT INTO d1 (id, d) VALUES (1, '2024-01-01'), (2, '2024-02-01');
+        INSERT INTO d2 (id, s) VALUES (1, '2024-01-01'), (2, '2024-03-05');").unwrap();
+    db
+}
+
+#[test]
+fn equi_joins_match_values_of_different_types_like_comparisons_do() {
+    let mut db = dated();
+    let hashed = fetch(&mut db, "SELECT d1.id, d2.id FROM d1 JOIN d2 ON d1.d = d2.s");
+    let compared = fetch(&mut db, "SELECT d1.id, d2.id FROM d1 JOIN d2 ON d1.d = d2.s OR 1 = 0");
+    assert_eq!(hashed.rows, vec![vec![Value::Int64(1), Value::Int64(1)]]);
+    assert_eq!(hashed.rows, compared.rows);
+    let left = fetch(&mut db, "SELECT d1.id, d2.id FROM d1 LEFT JOIN d2 ON d1.d = d2.s AND d1.id = d2.id ORDER BY d1.id");
+    assert_eq!(left.rows, vec![vec![Value::Int64(1), Value::Int64(1)], vec![Value::Int64(2), Value::Null]]);
+}
+
+fn catalog() -> DataBase {
+    let mut db = DataBase::new();
+    run(&mut db, "CREATE TABLE colors (name VARCHAR(10))").unwrap();
+    run(&mut db, "CREATE TABLE sizes (id INT PRIMARY KEY, label VARCHAR(2))").unwrap();
+    run(&mut db, "CREATE TABLE stock (size_id INT, color VARCHAR(10), units INT)").unwrap();
+    run(&mut db, "INSERT INTO colors (name) VALUES ('red'), ('blue')").unwrap();
+    run(&mut db, "INSERT INTO sizes (id, label) VALUES (1, 'S'), (2, 'M'), (3, 'L')").unwrap();
+    run(&mut db, "INSERT INTO stock (size_id, color, units) VALUES (1, 'red', 4), (3, 'red', 0), (3, 'blue', 7), (2, 'green', 1)").unwrap();
+    db
+}
+
+#[test]
+fn comma_separated_from_is_a_cross_product() {
+    let mut db = catalog();
+    let product = fetch(&mut db, "SELECT * FROM colors, sizes ORDER BY name, id");
+    assert_eq!(product.columns, vec!["name", "id", "label"]);
+    assert_eq!(product.rows.len(), 6);
+    assert_eq!(product.rows[0], vec![text("blue"), Value::Int64(1), text("S")]);
+    assert_eq!(product.rows[5], vec![text("red"), Value::Int64(3), text("L")]);
+}
+
+#[test]
+fn where_equalities_join_comma_separated_items() {
+    let mut db = catalog();
+    let in_stock = fetch(&mut db, "SELECT c.name, s.label, units FROM colors c, sizes s, stock \
+        WHERE stock.size_id = s.id AND stock.color = c.name AND units > 0 ORDER BY units");
+    assert_eq!(in_stock.rows, vec![vec![text("red"), text("S"), Value::Int64(4)], vec![text("blue"), text("L"), Value::Int64(7)]]);
+}
+
+#[test]
+fn comma_separated_items_mix_with_joins() {
+    let mut db = catalog();
+    let mixed = fetch(&mut db, "SELECT COUNT(*) FROM colors CROSS JOIN sizes, stock WHERE stock.color = colors.name OR sizes.id > 2");
+    assert_eq!(mixed.rows, vec![vec![Value::Int64(14)]]);
+}
+
+#[test]
+fn a_table_cannot_appear_twice_without_an_alias() {
+    let mut db = catalog();
+    assert!(matches!(run(&mut db, "SELECT id FROM sizes, sizes"), Err(DbError::Execution(_))));
+}
+
+#[test]
+fn where_equalities_match_values_of_different_types_like_comparisons_do() {
+    let mut db = dated();
+    let hashed = fetch(&mut db, "SELECT d1.id, d2.id FROM d1, d2 WHERE d1.d = d2.s");
+    let compared = fetch(&mut db, "SELECT d1.id, d2.id FROM d1, d2 WHERE d1.d = d2.s OR 1 = 0");
+    assert_eq!(hashed.rows, vec![vec![Value::Int64(1), Value::Int64(1)]]);
+    assert_eq!(hashed.rows, compared.rows);
+}
@@ -0,0 +1,160 @@
+mod common;
+
+use common::{fetch, ints, run, text, texts};
+use in_memory_db::data_descriptor::DataBase;
+use in_memory_db::error::DbError;
+use in_memory_db::value::Value;
+
+#[test]
+fn projection_names_columns_after_aliases_and_expressions() {
+    let mut db = DataBase::new();
+    run(&mut db, "CREATE TABLE users (id INT PRIMARY KEY, name VARCHAR(20), age INT);
+        INSERT INTO users (id, name, age) VALUES (1, 'alice', 30);").unwrap();
+    let result = fetch(&mut db, "SELECT name, users.age AS years, age * 2 + 1, UPPER(name) || '!' AS shout FROM users");
+    assert_eq!(result.columns, vec!["name", "years", "age * 2 + 1", "shout"]);
+    assert_eq!(result.rows, vec![vec![text("alice"), Value::Int64(30), Value::Int64(61), text("ALICE!")]]);
+}
+
+fn people() -> DataBase {
+    let mut db = DataBase::new();
+    run(&mut db, "CREATE TABLE users (id INT PRIMARY KEY, name VARCHAR(20), age INT NULL, city VARCHAR(20));
+        INSERT INTO users (city, age, name, id) VALUES ('Rome', 30, 'alice', 1), ('Oslo', 41, 'bob', 2);
+        INSERT INTO users (id, name, city) VALUES (3, 'carol', 'Lima');").unwrap();
+    db
+}
+
+#[test]
+fn wildcard_follows_declared_column_order() {
+    let mut db = people();
+    let result = fetch(&mut db, "SELECT *, id FROM users WHERE id = 3");
+    assert_eq!(result.columns, vec!["id", "name", "age", "city", "id"]);
+    assert_eq!(result.rows, vec![vec![Value::Int64(3), text("carol"), Value::Null, text("Lima"), Value::Int64(3)]]);
+}
+
+#[test]
+fn wildcard_order_ignores_insert_column_order() {
+    let mut db = people();
+    for row in fetch(&mut db, "SELECT * FROM users").rows {
+        assert!(matches!(row.as_slice(), [Value::Int64(_), Value::Text(_), _, Value::Text(_)]));
+    }
+}
+
+/// First column of every row of `sql`, in the order of the result.
+fn first_column(db: &mut DataBase, sql: &str) -> Vec<Value> {
+    fetch(db, sql).rows.into_iter().map(|mut row| row.remove(0)).collect()
+}
+
+fn members() -> DataBase {
+    let mut db = DataBase::new();
+    run(&mut db, "CREATE TABLE people (id INT PRIMARY KEY, name VARCHAR(20), age INT, joined DATE)").unwrap();
+    run(&mut db, "INSERT INTO people (id, name, age, joined) VALUES (1, 'bob', 30, '2021-03-01'), (2, 'amy', NULL, '2020-01-15'), \
+        (3, 'cid', 9, '2022-07-30'), (4, 'dan', 30, NULL), (10, 'eve', 100, '2019-12-31')").unwrap();
+    db
+}
+
+#[test]
+fn order_by_one_key_in_either_direction() {
+    let mut db = members();
+    assert_eq!(first_column(&mut db, "SELECT id FROM people ORDER BY id DESC"), ints(&[10, 4, 3, 2, 1]));
+    assert_eq!(first_column(&mut db, "SELECT id FROM people WHERE id > 1 ORDER BY id"), ints(&[2, 3, 4, 10]));
+}
+
+#[test]
+fn order_by_several_keys_sorts_numbers_numerically_and_nulls_last() {
+    let mut db = members();
+    assert_eq!(first_column(&mut db, "SELECT id FROM people ORDER BY age, name DESC"), ints(&[3, 4, 1, 10, 2]));
+}
+
+#[test]
+fn order_by_places_nulls_as_asked() {
+    let mut db = members();
+    assert_eq!(first_column(&mut db, "SELECT id FROM people ORDER BY age DESC NULLS LAST, id"), ints(&[10, 1, 4, 3, 2]));
+    assert_eq!(first_column(&mut db, "SELECT id FROM people ORDER BY joined NULLS FIRST"), ints(&[4, 10, 2, 1, 3]));
+}
+
+#[test]
+fn order_by_aliases_and_positions() {
+    let mut db = members();
+    assert_eq!(first_column(&mut db, "SELECT id, age * -1 AS neg FROM people WHERE age IS NOT NULL ORDER BY neg, 1 DESC"), ints(&[10, 4, 1, 3]));
+    assert_eq!(first_column(&mut db, "SELECT name FROM people ORDER BY 1 DESC"), texts(&["eve", "dan", "cid", "bob", "amy"]));
+    assert!(matches!(run(&mut db, "SELECT id FROM people ORDER BY 3"), Err(DbError::Parse(_))));
+}
+
+fn scores() -> DataBase {
+    let mut db = DataBase::new();
+    run(&mut db, "CREATE TABLE scores (id INT PRIMARY KEY, points INT)").unwrap();
+    run(&mut db, "INSERT INTO scores (id, points) VALUES (1, 50), (2, 80), (3, 80), (4, 20), (5, 95), (6, 80)").unwrap();
+    db
+}
+
+#[test]
+fn limit_and_offset_page_through_sorted_rows() {
+    let mut db = scores();
+    assert_eq!(first_column(&mut db, "SELECT id FROM scores ORDER BY points DESC, id LIMIT 3"), ints(&[5, 2, 3]));
+    assert_eq!(first_column(&mut db, "SELECT id FROM scores ORDER BY points DESC, id LIMIT 2 OFFSET 2"), ints(&[3, 6]));
+    assert_eq!(first_column(&mut db, "SELECT id FROM scores ORDER BY id LIMIT 2 OFFSET 3"), ints(&[4, 5]));
+    assert_eq!(first_column(&mut db, "SELECT id FROM scores ORDER BY points, id OFFSET 4 ROWS"), ints(&[6, 5]));
+}
+
+#[test]
+fn fetch_first_with_ties_keeps_the_peers_of_the_last_row() {
+    let mut db = scores();
+    let with_ties = first_column(&mut db, "SELECT id FROM scores ORDER BY points DESC FETCH FIRST 2 ROWS WITH TIES");
+    assert_eq!(with_ties[0], Value::Int64(5));
+    assert_eq!(with_ties[1..].iter().cloned().collect::<std::collections::BTreeSet<Value>>(), ints(&[2, 3, 6]).into_iter().collect());
+    assert_eq!(first_column(&mut db, "SELECT id FROM scores ORDER BY id DESC FETCH NEXT ROW ONLY"), ints(&[6]));
+}
+
+#[test]
+fn limit_without_order_and_past_the_end() {
+    let mut db = scores();
+    assert_eq!(first_column(&mut db, "SELECT id FROM scores WHERE points > 60 LIMIT 2").len(), 2);
+    assert_eq!(first_column(&mut db, "SELECT id FROM scores LIMIT 10 OFFSET 7"), vec![]);
+    assert_eq!(first_column(&mut db, "SELECT id FROM scores LIMIT 0"), vec![]);
+}
+
+#[test]
+fn negative_limit_is_an_error() {
+    let mut db = scores();
+    assert!(matches!(run(&mut db, "SELECT id FROM scores LIMIT -1"), Err(DbError::Execution(_))));
+}
+
+fn visits() -> DataBase {
+    let mut db = DataBase::new();
+    run(&mut db, "CREATE TABLE visits (id INT PRIMARY KEY, page VARCHAR(10), visitor VARCHAR(10), seconds INT)").unwrap();
+    run(&mut db, "INSERT INTO visits (id, page, visitor, seconds) VALUES (1, 'home', 'amy', 30), (2, 'home', 'bob', 12), (3, 'docs', 'amy', 45), \
+        (4, 'home', 'amy', 30), (5, 'docs', NULL, 8), (6, 'blog', NULL, 8)").unwrap();
+    db
+}
+
+#[test]
+fn distinct_removes_duplicate_rows() {
+    let mut db = visits();
+    assert_eq!(first_column(&mut db, "SELECT DISTINCT page FROM visits ORDER BY page"), texts(&["blog", "docs", "home"]));
+    assert_eq!(fetch(&mut db, "SELECT DISTINCT visitor, seconds FROM visits").rows.len(), 4);
+    assert_eq!(first_column(&mut db, "SELECT DISTINCT visitor FROM visits WHERE seconds < 10"), vec![Value::Null]);
+}
+
+#[test]
+fn distinct_applies_before_limit_and_offset() {
+    let mut db = visits();
+    assert_eq!(first_column(&mut db, "SELECT DISTINCT page FROM visits ORDER BY page DESC LIMIT 2 OFFSET 1"), texts(&["docs", "blog"]));
+}
+
+#[test]
+fn distinct_on_keeps_the_first_row_of_each_key() {
+    let mut db = visits();
+    let longest = fetch(&mut db, "SELECT DISTINCT ON (page) page, id FROM visits ORDER BY page, seconds DESC, id");
+    assert_eq!(longest.rows, vec![
+        vec![text("blog"), Value::Int64(6)],
+        vec![text("docs"), Value::Int64(3)],
+        vec![text("home"), Value::Int64(1)],
+    ]);
+    assert_eq!(first_column(&mut db, "SELECT DISTINCT ON (visitor) id FROM visits ORDER BY visitor NULLS LAST, id LIMIT 2"), ints(&[1, 2]));
+}
+
+#[test]
+fn distinct_on_positions_must_be_in_range() {
+    let mut db = visits();
+    assert!(matches!(run(&mut db, "SELECT DISTINCT ON (3) page FROM visits"), Err(DbError::Parse(_))));
+}
@@ -0,0 +1,66 @@
+mod common;
+
+use common::{column, fetch, run, texts};
+use in_memory_db::data_descriptor::DataBase;
+use in_memory_db::error::DbError;
+use in_memory_db::value::Value;
+
+fn stores() -> DataBase {
+    let mut db = DataBase::new();
+    run(&mut db, "CREATE TABLE north (item VARCHAR(10), qty INT)").unwrap();
+    run(&mut db, "CREATE TABLE south (item VARCHAR(10), qty INT)").unwrap();
+    run(&mut db, "INSERT INTO north (item, qty) VALUES ('tea', 1), ('tea', 1), ('jam', 2), ('oil', NULL)").unwrap();
+    run(&mut db, "INSERT INTO south (item, qty) VALUES ('tea', 1), ('rye', 4), ('oil', NULL)").unwrap();
+    db
+}
+
+/// First column of every row of `sql`.
+fn items(db: &mut DataBase, sql: &str) -> Vec<Value> {
+    column(&fetch(db, sql), 0)
+}
+
+#[test]
+fn union_takes_column_names_from_the_first_query() {
+    let mut db = stores();
+    let union = fetch(&mut db, "SELECT item AS name, qty FROM north UNION SELECT item, qty FROM south ORDER BY name");
+    assert_eq!(union.columns, vec!["name", "qty"]);
+    assert_eq!(column(&union, 0), texts(&["jam", "oil", "rye", "tea"]));
+}
+
+#[test]
+fn union_all_keeps_duplicates() {
+    let mut db = stores();
+    assert_eq!(fetch(&mut db, "SELECT item FROM north UNION ALL SELECT item FROM south").rows.len(), 7);
+    assert_eq!(items(&mut db, "SELECT item FROM north UNION ALL SELECT item FROM south ORDER BY 1 DESC LIMIT 2 OFFSET 1"), texts(&["tea", "tea"]));
+}
+
+#[test]
+fn intersect_matches_nulls_as_equal() {
+    let mut db = stores();
+    assert_eq!(items(&mut db, "SELECT item, qty FROM north INTERSECT SELECT item, qty FROM south ORDER BY item"), texts(&["oil", "tea"]));
+    assert_eq!(items(&mut db, "SELECT item FROM north INTERSECT ALL SELECT item FROM south UNION ALL SELECT 'tea' ORDER BY item"), texts(&["oil", "tea", "tea"]));
+}
+
+#[test]
+fn except_removes_rows_of_the_right_query() {
+    let mut db = stores();
+    assert_eq!(items(&mut db, "SELECT item FROM north EXCEPT SELECT item FROM south"), texts(&["jam"]));
+    assert_eq!(items(&mut db, "SELECT item FROM north EXCEPT ALL SELECT item FROM south ORDER BY item"), texts(&["jam", "tea"]));
+    assert_eq!(items(&mut db, "SELECT item FROM north EXCEPT (SELECT item FROM south UNION SELECT 'jam')"), vec![]);
+}
+
+#[test]
+fn set_operands_must_have_matching_columns() {
+    let mut db = stores();
+    assert!(matches!(run(&mut db, "SELECT item, qty FROM north UNION SELECT item FROM south"), Err(DbError::Execution(_))));
+    assert!(matches!(run(&mut db, "SELECT qty FROM north UNION SELECT item FROM south"), Err(DbError::Execution(_))));
+}
+
+#[test]
+fn set_operands_must_have_compatible_values_in_every_row() {
+    let mut db = stores();
+    let mixed = "SELECT CASE WHEN qty = 1 THEN qty ELSE 'many' END FROM north";
+    assert!(matches!(run(&mut db, &format!("{} UNION SELECT 1", mixed)), Err(DbError::Execution(_))));
+    assert!(matches!(run(&mut db, &format!("SELECT 1 UNION ALL {}", mixed)), Err(DbError::Execution(_))));
+    assert_eq!(fetch(&mut db, "SELECT qty FROM north UNION SELECT 2.5").rows.len(), 4);
+}
@@ -0,0 +1,115 @@
+mod common;
+
+use common::{column, fetch, ints, run, text, texts};
+use in_memory_db::data_descriptor::DataBase;
+use in_memory_db::error::DbError;
+use in_memory_db::value::Value;
+
+fn shop() -> DataBase {
+    let mut db = DataBase::new();
+    run(&mut db, "CREATE TABLE customers (id INT PRIMARY KEY, name VARCHAR(10), city VARCHAR(10))").unwrap();
+    run(&mut db, "CREATE TABLE orders (id INT PRIMARY KEY, customer_id INT, total INT)").unwrap();
+    run(&mut db, "INSERT INTO customers (id, name, city) VALUES (1, 'amy', 'oslo'), (2, 'bob', 'rome'), (3, 'cid', 'oslo'), (4, 'dan', 'lima')").unwrap();
+    run(&mut db, "INSERT INTO orders (id, customer_id, total) VALUES (10, 1, 50), (11, 1, 250), (12, 2, 80), (13, 3, 120), (14, NULL, 10)").unwrap();
+    db
+}
+
+/// First column of every row of `sql`.
+fn first_column(db: &mut DataBase, sql: &str) -> Vec<Value> {
+    column(&fetch(db, sql), 0)
+}
+
+#[test]
+fn scalar_subqueries_yield_one_value() {
+    let mut db = shop();
+    let counts = fetch(&mut db, "SELECT name, (SELECT COUNT(*) FROM orders o WHERE o.customer_id = c.id) AS n FROM customers c ORDER BY id");
+    assert_eq!(counts.columns, vec!["name", "n"]);
+    assert_eq!(column(&counts, 1), ints(&[2, 1, 1, 0]));
+    assert_eq!(first_column(&mut db, "SELECT id FROM orders WHERE total > (SELECT AVG(total) FROM orders) ORDER BY id"), ints(&[11, 13]));
+    assert_eq!(first_column(&mut db, "SELECT (SELECT MAX(total) FROM orders)"), ints(&[250]));
+}
+
+#[test]
+fn in_subqueries_follow_three_valued_logic() {
+    let mut db = shop();
+    assert_eq!(first_column(&mut db, "SELECT name FROM customers WHERE id IN (SELECT customer_id FROM orders WHERE total > 100) ORDER BY name"), texts(&["amy", "cid"]));
+    assert_eq!(first_column(&mut db, "SELECT name FROM customers WHERE id NOT IN (SELECT customer_id FROM orders)"), vec![]);
+    assert_eq!(first_column(&mut db, "SELECT name FROM customers WHERE id NOT IN (SELECT customer_id FROM orders WHERE customer_id IS NOT NULL)"), texts(&["dan"]));
+}
+
+#[test]
+fn exists_subqueries_see_the_outer_row() {
+    let mut db = shop();
+    assert_eq!(first_column(&mut db, "SELECT name FROM customers WHERE EXISTS (SELECT 1 FROM orders WHERE customer_id = customers.id AND total < 100) ORDER BY name"),
+        texts(&["amy", "bob"]));
+    assert_eq!(first_column(&mut db, "SELECT name FROM customers c WHERE NOT EXISTS (SELECT * FROM orders o WHERE o.customer_id = c.id)"), texts(&["dan"]));
+    assert_eq!(first_column(&mut db, "SELECT city FROM customers c GROUP BY city HAVING COUNT(*) > (SELECT COUNT(*) FROM orders WHERE total > 200)"), texts(&["oslo"]));
+}
+
+#[test]
+fn derived_tables_act_as_relations() {
+    let mut db = shop();
+    let per_city = fetch(&mut db, "SELECT t.city, t.n FROM (SELECT city, COUNT(*) AS n FROM customers GROUP BY city) AS t WHERE t.n > 1");
+    assert_eq!(per_city.rows, vec![vec![text("oslo"), Value::Int64(2)]]);
+    let spent = fetch(&mut db, "SELECT c.name, s.spent FROM customers c JOIN (SELECT customer_id, SUM(total) FROM orders GROUP BY customer_id) AS s (cid, spent) \
+        ON s.cid = c.id WHERE s.spent >= 100 ORDER BY s.spent DESC");
+    assert_eq!(spent.rows, vec![vec![text("amy"), Value::Int64(300)], vec![text("cid"), Value::Int64(120)]]);
+    assert_eq!(first_column(&mut db, "SELECT * FROM (SELECT name FROM customers WHERE id < 3) AS x ORDER BY name"), texts(&["amy", "bob"]));
+}
+
+#[test]
+fn subqueries_of_the_wrong_shape_are_refused() {
+    let mut db = shop();
+    assert!(matches!(run(&mut db, "SELECT (SELECT id FROM orders)"), Err(DbError::Execution(_))));
+    assert!(matches!(run(&mut db, "SELECT name FROM customers WHERE id IN (SELECT id, total FROM orders)"), Err(DbError::Execution(_))));
+    assert!(matches!(run(&mut db, "SELECT name FROM customers WHERE EXISTS (SELECT 1 FROM orders WHERE nope = 1)"), Err(DbError::UnknownColumn(_))));
+}
+
+fn hierarchy() -> DataBase {
+    let mut db = DataBase::new();
+    run(&mut db, "CREATE TABLE employees (id INT PRIMARY KEY, name VARCHAR(10), manager_id INT, salary INT)").unwrap();
+    run(&mut db, "INSERT INTO employees (id, name, manager_id, salary) VALUES (1, 'ceo', NULL, 500), (2, 'cto', 1, 300), (3, 'cfo', 1, 280), \
+        (4, 'dev', 2, 150), (5, 'ops', 2, 140), (6, 'intern', 4, 50)").unwrap();
+    run(&mut db, "CREATE TABLE links (src INT, dst INT)").unwrap();
+    run(&mut db, "INSERT INTO links (src, dst) VALUES (1, 2), (2, 3), (3, 1)").unwrap();
+    db
+}
+
+#[test]
+fn recursive_union_all_walks_a_hierarchy() {
+    let mut db = hierarchy();
+    let reports = fetch(&mut db, "WITH RECURSIVE chain (id, name, depth) AS (SELECT id, name, 0 FROM employees WHERE manager_id IS NULL \
+        UNION ALL SELECT e.id, e.name, c.depth + 1 FROM employees e JOIN chain c ON e.manager_id = c.id) SELECT name, depth FROM chain ORDER BY depth DESC, name LIMIT 3");
+    assert_eq!(reports.columns, vec!["name", "depth"]);
+    assert_eq!(reports.rows, vec![vec![text("intern"), Value::Int64(3)], vec![text("dev"), Value::Int64(2)], vec![text("ops"), Value::Int64(2)]]);
+
+    let counted = fetch(&mut db, "WITH RECURSIVE n (x) AS (SELECT 1 UNION ALL SELECT x + 1 FROM n WHERE x < 50) SELECT COUNT(*), MAX(x) FROM n");
+    assert_eq!(counted.rows, vec![vec![Value::Int64(50), Value::Int64(50)]]);
+}
+
+#[test]
+fn recursive_ctes_can_be_used_in_subqueries() {
+    let mut db = hierarchy();
+    let under_cto = fetch(&mut db, "WITH RECURSIVE team AS (SELECT id FROM employees WHERE name = 'cto' UNION SELECT e.id FROM employees e, team t WHERE e.manager_id = t.id) \
+        SELECT SUM(salary) FROM employees WHERE id IN (SELECT id FROM team)");
+    assert_eq!(under_cto.rows, vec![vec![Value::Int64(640)]]);
+}
+
+#[test]
+fn recursive_union_stops_on_cycles_and_union_all_does_not() {
+    let mut db = hierarchy();
+    assert_eq!(first_column(&mut db, "WITH RECURSIVE r (node) AS (SELECT 1 UNION SELECT dst FROM links JOIN r ON src = node) SELECT node FROM r ORDER BY node"),
+        ints(&[1, 2, 3]));
+    assert!(matches!(run(&mut db, "WITH RECURSIVE r (node) AS (SELECT 1 UNION ALL SELECT dst FROM links JOIN r ON src = node) SELECT node FROM r"),
+        Err(DbError::Execution(_))));
+}
+
+#[test]
+fn ctes_see_earlier_ctes_and_shadow_tables() {
+    let mut db = hierarchy();
+    let pairs = fetch(&mut db, "WITH seniors AS (SELECT id, name, salary FROM employees WHERE salary >= 280), \
+        paid (who, pay) AS (SELECT name, salary FROM seniors) \
+        SELECT a.who, b.who FROM paid a JOIN paid b ON a.pay > b.pay WHERE b.who = (SELECT name FROM seniors ORDER BY salary LIMIT 1) ORDER BY a.pay");
+    assert_eq!(pairs.rows, vec![vec![text("cto"), text("cfo")], vec![text("ceo"), text("cfo")]]);
+    assert_eq!(first_column(&mut db, "WITH employees AS (SELECT 7 AS id) SELECT id FROM employees"), ints(&[7]));
+}
@@ -0,0 +1,47 @@
+mod common;
+
+use common::{fetch, run};
+use in_memory_db::data_descriptor::DataBase;
+use in_memory_db::value::{Decimal, Rounding, Value};
+
+#[test]
+fn insert_stores_typed_values() {
+    let mut db = DataBase::new();
+    run(&mut db, "CREATE TABLE items (id INT PRIMARY KEY, price DECIMAL(10,2), active BOOLEAN, added DATE);
+        INSERT INTO items (id, price, active, added) VALUES (7, 12.50, true, '2024-02-29');").unwrap();
+    let row = fetch(&mut db, "SELECT id, price, active, added FROM items").rows.remove(0);
+    assert_eq!(row[0], Value::Int64(7));
+    assert!(matches!(row[1], Value::Decimal(Decimal { mantissa: 1250, scale: 2 })), "{:?}", row[1]);
+    assert_eq!(row[2], Value::Bool(true));
+    assert!(matches!(row[3], Value::Date(_)));
+}
+
+#[test]
+fn equal_numbers_of_different_kinds_are_distinct_once() {
+    let mut db = DataBase::new();
+    let result = fetch(&mut db, "SELECT DISTINCT x FROM (SELECT 9007199254740993 AS x UNION ALL SELECT CAST(9007199254740992 AS DOUBLE)) AS t");
+    assert_eq!(result.rows.len(), 1);
+    let result = fetch(&mut db, "SELECT DISTINCT x FROM (SELECT 0.50 AS x UNION ALL SELECT 0.5 UNION ALL SELECT CAST(0.5 AS DOUBLE)) AS t");
+    assert_eq!(result.rows.len(), 1);
+}
+
+#[test]
+fn decimal_keeps_its_scale_at_the_smallest_mantissa() {
+    let mut db = DataBase::new();
+    let row = fetch(&mut db, "SELECT CAST('-92233720368547758.08' AS DECIMAL(19,2))").rows.remove(0);
+    assert!(matches!(row[0], Value::Decimal(Decimal { mantissa: i64::MIN, scale: 2 })), "{:?}", row[0]);
+    assert_eq!(row[0].to_string(), "-92233720368547758.08");
+    assert_eq!(Decimal::parse("-9223372036854775809"), None);
+}
+
+#[test]
+fn decimal_rounds_to_fewer_digits_only() {
+    let decimal = |value_str: &str| Decimal::parse(value_str).unwrap();
+    assert_eq!(decimal("2.345").round_to(2, Rounding::HalfAwayFromZero).unwrap().to_string(), "2.35");
+    assert_eq!(decimal("-2.345").round_to(2, Rounding::HalfAwayFromZero).unwrap().to_string(), "-2.35");
+    assert_eq!(decimal("-1.5").round_to(0, Rounding::Down).unwrap().to_string(), "-2");
+    assert_eq!(decimal("1.2").round_to(0, Rounding::Up).unwrap().to_string(), "2");
+    assert_eq!(decimal("1250.5").round_to(-2, Rounding::HalfAwayFromZero).unwrap().to_string(), "1300");
+    assert_eq!(decimal("1.5").round_to(4, Rounding::HalfAwayFromZero).unwrap().to_string(), "1.5");
+    assert_eq!(Decimal { mantissa: i64::MAX, scale: 0 }.round_to(-1, Rounding::Up), None);
+}
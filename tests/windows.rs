@@ -0,0 +1,80 @@
+mod common;
+
+use common::{column, fetch, ints, run, text};
+use in_memory_db::data_descriptor::DataBase;
+use in_memory_db::error::DbError;
+use in_memory_db::value::Value;
+
+fn sales() -> DataBase {
+    let mut db = DataBase::new();
+    run(&mut db, "CREATE TABLE sales (region VARCHAR(10), day INT, amount INT)").unwrap();
+    run(&mut db, "INSERT INTO sales (region, day, amount) VALUES ('east', 1, 10), ('east', 2, 20), ('east', 3, 20), ('east', 4, 40), ('west', 1, 5), ('west', 3, 15)").unwrap();
+    db
+}
+
+#[test]
+fn ranking_functions_number_rows_within_partitions() {
+    let mut db = sales();
+    let ranks = fetch(&mut db, "SELECT ROW_NUMBER() OVER (PARTITION BY region ORDER BY amount DESC, day), RANK() OVER w, DENSE_RANK() OVER w \
+        FROM sales WINDOW w AS (PARTITION BY region ORDER BY amount DESC) ORDER BY region, day");
+    assert_eq!(column(&ranks, 0), ints(&[4, 2, 3, 1, 2, 1]));
+    assert_eq!(column(&ranks, 1), ints(&[4, 2, 2, 1, 2, 1]));
+    assert_eq!(column(&ranks, 2), ints(&[3, 2, 2, 1, 2, 1]));
+}
+
+#[test]
+fn lag_and_lead_read_neighbouring_rows() {
+    let mut db = sales();
+    let offsets = fetch(&mut db, "SELECT LAG(amount) OVER (PARTITION BY region ORDER BY day), LEAD(amount, 2, 0) OVER (PARTITION BY region ORDER BY day) \
+        FROM sales ORDER BY region, day");
+    assert_eq!(column(&offsets, 0), vec![Value::Null, Value::Int64(10), Value::Int64(20), Value::Int64(20), Value::Null, Value::Int64(5)]);
+    assert_eq!(column(&offsets, 1), ints(&[20, 40, 0, 0, 0, 0]));
+}
+
+#[test]
+fn default_frame_ends_with_the_last_peer() {
+    let mut db = sales();
+    let running = fetch(&mut db, "SELECT SUM(amount) OVER (PARTITION BY region ORDER BY amount), COUNT(*) OVER () FROM sales ORDER BY region, day");
+    assert_eq!(column(&running, 0), ints(&[10, 50, 50, 90, 5, 20]));
+    assert_eq!(column(&running, 1), ints(&[6, 6, 6, 6, 6, 6]));
+}
+
+#[test]
+fn rows_and_range_frames() {
+    let mut db = sales();
+    let sliding = fetch(&mut db, "SELECT FIRST_VALUE(amount) OVER w, LAST_VALUE(amount) OVER w, SUM(amount) OVER w FROM sales WHERE region = 'east' \
+        WINDOW w AS (ORDER BY day ROWS BETWEEN 1 PRECEDING AND 1 FOLLOWING) ORDER BY day");
+    assert_eq!(column(&sliding, 0), ints(&[10, 10, 20, 20]));
+    assert_eq!(column(&sliding, 1), ints(&[20, 20, 40, 40]));
+    assert_eq!(column(&sliding, 2), ints(&[30, 50, 80, 60]));
+    let by_days = fetch(&mut db, "SELECT SUM(amount) OVER (ORDER BY day RANGE BETWEEN 1 PRECEDING AND CURRENT ROW) AS total FROM sales ORDER BY region, day");
+    assert_eq!(column(&by_days, 0), ints(&[15, 35, 55, 75, 15, 55]));
+}
+
+#[test]
+fn windows_over_grouped_rows() {
+    let mut db = sales();
+    let grouped = fetch(&mut db, "SELECT region, SUM(amount), RANK() OVER (ORDER BY SUM(amount) DESC) FROM sales GROUP BY region ORDER BY 3 DESC");
+    assert_eq!(grouped.rows, vec![
+        vec![text("west"), Value::Int64(20), Value::Int64(2)],
+        vec![text("east"), Value::Int64(90), Value::Int64(1)],
+    ]);
+}
+
+#[test]
+fn misplaced_or_unsupported_windows_are_refused() {
+    let mut db = sales();
+    assert!(matches!(run(&mut db, "SELECT day FROM sales WHERE ROW_NUMBER() OVER (ORDER BY day) > 1"), Err(DbError::Execution(_))));
+    assert!(matches!(run(&mut db, "SELECT RANK() OVER missing FROM sales"), Err(DbError::Parse(_))));
+    assert!(matches!(run(&mut db, "SELECT SUM(amount) OVER (ORDER BY day GROUPS 1 PRECEDING) FROM sales"), Err(DbError::Unsupported(_))));
+}
+
+#[test]
+fn window_order_places_nulls_like_order_by() {
+    let mut db = sales();
+    run(&mut db, "INSERT INTO sales (region, day, amount) VALUES ('north', NULL, 1), ('north', 2, 2), ('north', 5, 3)").unwrap();
+    let numbered = fetch(&mut db, "SELECT ROW_NUMBER() OVER (ORDER BY day DESC), ROW_NUMBER() OVER (ORDER BY day NULLS FIRST) \
+        FROM sales WHERE region = 'north' ORDER BY day DESC");
+    assert_eq!(column(&numbered, 0), ints(&[1, 2, 3]));
+    assert_eq!(column(&numbered, 1), ints(&[1, 3, 2]));
+}